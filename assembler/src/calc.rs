@@ -0,0 +1,116 @@
+use crate::lexer::{parse_number, Token};
+
+/// Evaluates a `:calc` or `:byte` expression. Like Octo, operators have no precedence and are
+/// evaluated right to left, so `2 * 3 + 1` is 8; parentheses group as usual. `lookup` resolves
+/// names to constants, labels and `HERE`.
+pub fn evaluate(tokens: &[Token], lookup: &dyn Fn(&str) -> Option<f64>) -> Result<f64, String> {
+    let mut calc = Calc {
+        tokens,
+        at: 0,
+        lookup,
+    };
+    let value = calc.expression()?;
+    match calc.tokens.get(calc.at) {
+        Some(token) => Err(format!("Unexpected \"{}\" in expression.", token.text)),
+        None => Ok(value),
+    }
+}
+
+struct Calc<'a> {
+    tokens: &'a [Token],
+    at: usize,
+    lookup: &'a dyn Fn(&str) -> Option<f64>,
+}
+
+impl Calc<'_> {
+    fn next(&mut self) -> Result<&str, String> {
+        let token = self.tokens.get(self.at).ok_or("Incomplete expression.")?;
+        self.at += 1;
+        Ok(&token.text)
+    }
+
+    fn expression(&mut self) -> Result<f64, String> {
+        let left = self.term()?;
+        let Some(operator) = self.tokens.get(self.at).map(|token| token.text.as_str()) else {
+            return Ok(left);
+        };
+        if operator == ")" {
+            return Ok(left);
+        }
+        self.at += 1;
+        let right = self.expression()?;
+
+        let (a, b) = (left as i64, right as i64);
+        let truth = |condition: bool| condition as i64 as f64;
+        Ok(match operator {
+            "+" => left + right,
+            "-" => left - right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => a.checked_shl(b as u32).unwrap_or(0) as f64,
+            ">>" => a.checked_shr(b as u32).unwrap_or(0) as f64,
+            "<" => truth(left < right),
+            ">" => truth(left > right),
+            "<=" => truth(left <= right),
+            ">=" => truth(left >= right),
+            "==" => truth(left == right),
+            "!=" => truth(left != right),
+            _ => return Err(format!("Unknown operator \"{}\".", operator)),
+        })
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let token = self.next()?.to_string();
+        if token == "(" {
+            let value = self.expression()?;
+            return match self.next()? {
+                ")" => Ok(value),
+                other => Err(format!("Expected \")\", found \"{}\".", other)),
+            };
+        }
+
+        let unary: Option<fn(f64) -> f64> = match token.as_str() {
+            "-" => Some(|x| -x),
+            "~" => Some(|x| !(x as i64) as f64),
+            "!" => Some(|x| (x == 0.0) as i64 as f64),
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "floor" => Some(f64::floor),
+            "ceil" => Some(f64::ceil),
+            "sign" => Some(f64::signum),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "exp" => Some(f64::exp),
+            "log" => Some(f64::ln),
+            _ => None,
+        };
+        if let Some(function) = unary {
+            return Ok(function(self.term()?));
+        }
+
+        match token.as_str() {
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            _ => parse_number(&token)
+                .map(|value| value as f64)
+                .or_else(|| parse_float(&token))
+                .or_else(|| (self.lookup)(&token))
+                .ok_or_else(|| format!("Unknown name \"{}\" in expression.", token)),
+        }
+    }
+}
+
+// Plain `str::parse` would also take names like "inf"
+fn parse_float(text: &str) -> Option<f64> {
+    text.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        .then(|| text.parse().ok())
+        .flatten()
+}
@@ -0,0 +1,679 @@
+//! Assembler for [Octo](https://github.com/JohnEarnest/Octo), the assembly language most modern
+//! CHIP-8 programs are written in. It covers the CHIP-8 instruction set, along with Octo's labels,
+//! `:const`, `:alias`, `:calc`, `:macro`, `:byte`, `:org`, `:unpack` and structured control flow:
+//! `loop`/`while`/`again`, `if … then` and `if … begin … else … end`. SUPER-CHIP and XO-CHIP
+//! instructions are rejected, as the emulator doesn't run them.
+//!
+//! ```text
+//! : main
+//!   v0 := 0
+//!   loop
+//!     v0 += 1
+//!     if v0 == 10 then v0 := 0
+//!   again
+//! ```
+
+mod calc;
+mod lexer;
+
+use core::RAM_SIZE;
+
+use lexer::{parse_number, parse_register, Token};
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+const START_ADDR: usize = 0x200;
+// Stops recursive macros, which would otherwise expand forever
+const MAX_EXPANSIONS: usize = 100_000;
+
+// Names Octo knows, but which need an interpreter with more than CHIP-8
+const UNSUPPORTED: [&str; 14] = [
+    "hires",
+    "lores",
+    "scroll-down",
+    "scroll-up",
+    "scroll-left",
+    "scroll-right",
+    "exit",
+    "saveflags",
+    "loadflags",
+    "plane",
+    "audio",
+    "pitch",
+    "bighex",
+    "long",
+];
+
+/// Why a program failed to assemble, and where.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Assembles Octo source into a ROM, to be loaded at 0x200 with `Emulator::load`. Execution
+/// starts at the `main` label: unless it's the first thing in the program, the ROM begins with a
+/// jump to it.
+pub fn assemble(source: &str) -> std::result::Result<Vec<u8>, Error> {
+    let mut assembler = Assembler::new(lexer::tokenize(source));
+    let result = assembler.run();
+    result.map_err(|message| Error {
+        line: assembler.line,
+        message,
+    })?;
+    Ok(assembler.rom)
+}
+
+type Result<T> = std::result::Result<T, String>;
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+// A reference to a label defined further down, patched once the whole program is read
+struct Fixup {
+    at: usize,
+    label: String,
+    line: usize,
+    kind: FixupKind,
+}
+
+enum FixupKind {
+    /// The low 12 bits of the instruction at `at`.
+    Address,
+    /// The two `vX := NN` instructions of an `:unpack`, with the given high nybble.
+    Unpack(u8),
+}
+
+enum Flow {
+    Loop { start: usize, exits: Vec<usize> },
+    Branch { jump: usize, has_else: bool },
+}
+
+// An `if` or `while` condition. `setup` runs first, then one of the skips, depending on whether
+// the next instruction has to be skipped when the condition is false or when it's true
+struct Condition {
+    setup: Vec<u16>,
+    skip_if_false: u16,
+    skip_if_true: u16,
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    line: usize,
+    rom: Vec<u8>,
+    here: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    flow: Vec<(Flow, usize)>,
+    expansions: usize,
+}
+
+impl Assembler {
+    fn new(tokens: Vec<Token>) -> Assembler {
+        Assembler {
+            tokens: tokens.into(),
+            line: 1,
+            rom: Vec::new(),
+            here: START_ADDR,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            flow: Vec::new(),
+            expansions: 0,
+        }
+    }
+
+    fn run(&mut self) -> Result<()> {
+        let starts_with_main =
+            self.tokens.len() >= 2 && self.tokens[0].text == ":" && self.tokens[1].text == "main";
+        if !starts_with_main {
+            self.fixup("main", FixupKind::Address);
+            self.emit(0x1000)?;
+        }
+
+        while let Some(token) = self.tokens.pop_front() {
+            self.line = token.line;
+            self.statement(&token.text)?;
+        }
+
+        if let Some((flow, line)) = self.flow.last() {
+            self.line = *line;
+            return Err(match flow {
+                Flow::Loop { .. } => "This loop is missing its \"again\".".to_string(),
+                Flow::Branch { .. } => "This \"begin\" is missing its \"end\".".to_string(),
+            });
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.line = fixup.line;
+            let address = *self
+                .labels
+                .get(&fixup.label)
+                .ok_or_else(|| format!("Undefined label \"{}\".", fixup.label))?;
+            let at = fixup.at - START_ADDR;
+            match fixup.kind {
+                FixupKind::Address => {
+                    self.rom[at] |= (address >> 8) as u8;
+                    self.rom[at + 1] = address as u8;
+                }
+                FixupKind::Unpack(nybble) => {
+                    self.rom[at + 1] = (nybble << 4) | (address >> 8) as u8;
+                    self.rom[at + 3] = address as u8;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, token: &str) -> Result<()> {
+        match token {
+            ":" => {
+                let name = self.next()?;
+                if self.labels.insert(name.clone(), self.here).is_some() {
+                    return Err(format!("Label \"{}\" is already defined.", name));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.next()?;
+                let value = self
+                    .value(&value)
+                    .ok_or_else(|| format!("Expected a number, found \"{}\".", value))?;
+                self.constants.insert(name, value as f64);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":calc" => {
+                let name = self.next()?;
+                let expression = self.block()?;
+                let value = self.calc(&expression)?;
+                self.constants.insert(name, value);
+            }
+            ":macro" => {
+                let name = self.next()?;
+                let mut params = Vec::new();
+                while self.tokens.front().is_some_and(|token| token.text != "{") {
+                    params.push(self.next()?);
+                }
+                let body = self.block()?;
+                self.macros.insert(name, Macro { params, body });
+            }
+            ":byte" => {
+                let value = if self.tokens.front().is_some_and(|token| token.text == "{") {
+                    let expression = self.block()?;
+                    self.calc(&expression)? as i64
+                } else {
+                    let value = self.next()?;
+                    self.value(&value)
+                        .ok_or_else(|| format!("Expected a number, found \"{}\".", value))?
+                };
+                let byte = check_byte(value)?;
+                self.write(byte)?;
+            }
+            ":org" => {
+                let address = self.next()?;
+                self.here = match self.value(&address) {
+                    Some(address) if (START_ADDR as i64..RAM_SIZE as i64).contains(&address) => {
+                        address as usize
+                    }
+                    _ => return Err(format!("Invalid address \"{}\".", address)),
+                };
+            }
+            ":unpack" => {
+                let nybble = self.next()?;
+                let nybble = match self.value(&nybble) {
+                    Some(nybble) if (0..=0xF).contains(&nybble) => nybble as u8,
+                    _ => return Err(format!("Expected a nybble, found \"{}\".", nybble)),
+                };
+                let label = self.next()?;
+                let address = match self.value(&label) {
+                    Some(address) => check_address(address)?,
+                    None => {
+                        self.fixup(&label, FixupKind::Unpack(nybble));
+                        0
+                    }
+                };
+                self.emit(0x6000 | ((nybble as u16) << 4) | (address >> 8))?;
+                self.emit(0x6100 | (address & 0xFF))?;
+            }
+            // Debugger hints, which don't change the program
+            ":breakpoint" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "clear" => self.emit(0x00E0)?,
+            "return" | ";" => self.emit(0x00EE)?,
+            "bcd" => self.register_op(0xF033)?,
+            "save" => self.register_range_op(0xF055)?,
+            "load" => self.register_range_op(0xF065)?,
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let height = self.next()?;
+                let height = match self.value(&height) {
+                    Some(height) if (0..=0xF).contains(&height) => height as u16,
+                    _ => return Err(format!("Invalid sprite height \"{}\".", height)),
+                };
+                self.emit(0xD000 | (x << 8) | (y << 4) | height)?;
+            }
+            "jump" => self.address_op(0x1000)?,
+            "jump0" => self.address_op(0xB000)?,
+            "native" => self.address_op(0x0000)?,
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                self.register_op(if token == "delay" { 0xF015 } else { 0xF018 })?;
+            }
+            "i" => match self.next()?.as_str() {
+                ":=" if self.tokens.front().is_some_and(|token| token.text == "hex") => {
+                    self.next()?;
+                    self.register_op(0xF029)?;
+                }
+                ":=" => self.address_op(0xA000)?,
+                "+=" => self.register_op(0xF01E)?,
+                other => return Err(format!("Unknown operator \"{}\" for i.", other)),
+            },
+            "loop" => self.flow.push((
+                Flow::Loop {
+                    start: self.here,
+                    exits: Vec::new(),
+                },
+                self.line,
+            )),
+            "while" => {
+                let condition = self.condition()?;
+                self.emit_condition(&condition, condition.skip_if_true)?;
+                let jump = self.here;
+                self.emit(0x1000)?;
+                let innermost = self.flow.iter_mut().rev().find_map(|(flow, _)| match flow {
+                    Flow::Loop { exits, .. } => Some(exits),
+                    Flow::Branch { .. } => None,
+                });
+                innermost.ok_or("\"while\" outside of a loop.")?.push(jump);
+            }
+            "again" => match self.flow.pop() {
+                Some((Flow::Loop { start, exits }, _)) => {
+                    self.emit(0x1000 | start as u16)?;
+                    for exit in exits {
+                        self.patch_jump(exit, self.here);
+                    }
+                }
+                _ => return Err("\"again\" without a loop.".to_string()),
+            },
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()?.as_str() {
+                    "then" => self.emit_condition(&condition, condition.skip_if_false)?,
+                    "begin" => {
+                        self.emit_condition(&condition, condition.skip_if_true)?;
+                        let jump = self.here;
+                        self.emit(0x1000)?;
+                        let branch = Flow::Branch {
+                            jump,
+                            has_else: false,
+                        };
+                        self.flow.push((branch, self.line));
+                    }
+                    other => {
+                        return Err(format!(
+                            "Expected \"then\" or \"begin\", found \"{}\".",
+                            other
+                        ))
+                    }
+                }
+            }
+            "else" => {
+                let Some((Flow::Branch { jump, has_else }, _)) = self.flow.last_mut() else {
+                    return Err("\"else\" without a \"begin\".".to_string());
+                };
+                if *has_else {
+                    return Err("A \"begin\" can only have one \"else\".".to_string());
+                }
+                let skip = *jump;
+                *jump = self.here;
+                *has_else = true;
+                self.emit(0x1000)?;
+                self.patch_jump(skip, self.here);
+            }
+            "end" => match self.flow.pop() {
+                Some((Flow::Branch { jump, .. }, _)) => self.patch_jump(jump, self.here),
+                _ => return Err("\"end\" without a \"begin\".".to_string()),
+            },
+            "then" | "begin" | "{" | "}" => return Err(format!("Unexpected \"{}\".", token)),
+            _ if UNSUPPORTED.contains(&token) => {
+                return Err(format!("\"{}\" needs SUPER-CHIP or XO-CHIP.", token))
+            }
+            _ if token.starts_with(':') => return Err(format!("Unknown directive \"{}\".", token)),
+            _ => {
+                if let Some(x) = self.lookup_register(token) {
+                    return self.register_statement(x);
+                }
+                if self.macros.contains_key(token) {
+                    return self.expand(token);
+                }
+                // Labels are subroutine calls, even when already defined; bare numbers are data,
+                // e.g. sprites
+                if self.labels.contains_key(token) {
+                    return self.call(token);
+                }
+                match self.value(token) {
+                    Some(value) => {
+                        let byte = check_byte(value)?;
+                        self.write(byte)?;
+                    }
+                    None => self.call(token)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<()> {
+        let x = (x as u16) << 8;
+        let operator = self.next()?;
+        let operand = self.next()?;
+        let y = self.lookup_register(&operand).map(|y| (y as u16) << 4);
+
+        let op = match (operator.as_str(), y) {
+            (":=", Some(y)) => 0x8000 | x | y,
+            (":=", None) => match operand.as_str() {
+                "random" => {
+                    let mask = self.next()?;
+                    0xC000 | x | self.byte(&mask)? as u16
+                }
+                "key" => 0xF00A | x,
+                "delay" => 0xF007 | x,
+                _ => 0x6000 | x | self.byte(&operand)? as u16,
+            },
+            ("+=", Some(y)) => 0x8004 | x | y,
+            ("+=", None) => 0x7000 | x | self.byte(&operand)? as u16,
+            ("-=", Some(y)) => 0x8005 | x | y,
+            ("-=", None) => 0x7000 | x | self.byte(&operand)?.wrapping_neg() as u16,
+            ("=-", Some(y)) => 0x8007 | x | y,
+            ("|=", Some(y)) => 0x8001 | x | y,
+            ("&=", Some(y)) => 0x8002 | x | y,
+            ("^=", Some(y)) => 0x8003 | x | y,
+            (">>=", Some(y)) => 0x8006 | x | y,
+            ("<<=", Some(y)) => 0x800E | x | y,
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", None) => {
+                return Err(format!("Expected a register, found \"{}\".", operand))
+            }
+            _ => return Err(format!("Unknown operator \"{}\".", operator)),
+        };
+        self.emit(op)
+    }
+
+    fn condition(&mut self) -> Result<Condition> {
+        let x = self.register()? as u16;
+        let operator = self.next()?;
+        let (skip_if_false, skip_if_true) = match operator.as_str() {
+            "key" => (0xE0A1, 0xE09E),
+            "-key" => (0xE09E, 0xE0A1),
+            _ => (0, 0),
+        };
+        if skip_if_false != 0 {
+            return Ok(Condition {
+                setup: Vec::new(),
+                skip_if_false: skip_if_false | (x << 8),
+                skip_if_true: skip_if_true | (x << 8),
+            });
+        }
+
+        let operand = self.next()?;
+        let y = self.lookup_register(&operand).map(|y| y as u16);
+        let n = match y {
+            Some(_) => 0,
+            None => self.byte(&operand)? as u16,
+        };
+
+        // SNE/SE and their register forms skip the next instruction when the operands differ or
+        // match, so an equality needs the one that skips when it doesn't hold
+        let (equal, not_equal) = match y {
+            Some(y) => (0x5000 | (x << 8) | (y << 4), 0x9000 | (x << 8) | (y << 4)),
+            None => (0x3000 | (x << 8) | n, 0x4000 | (x << 8) | n),
+        };
+        // Orderings have no instruction of their own: they subtract into VF and test its borrow
+        // flag, which is set when the left operand is greater than or equal to the right one
+        let x_ge_y = match y {
+            Some(y) => vec![0x8F00 | (x << 4), 0x8F05 | (y << 4)],
+            None => vec![0x6F00 | n, 0x8F07 | (x << 4)],
+        };
+        let y_ge_x = match y {
+            Some(y) => vec![0x8F00 | (x << 4), 0x8F07 | (y << 4)],
+            None => vec![0x6F00 | n, 0x8F05 | (x << 4)],
+        };
+        let (flag_set, flag_clear) = (0x3F00, 0x4F00);
+
+        let (setup, skip_if_false, skip_if_true) = match operator.as_str() {
+            "==" => (Vec::new(), not_equal, equal),
+            "!=" => (Vec::new(), equal, not_equal),
+            ">=" => (x_ge_y, flag_set, flag_clear),
+            "<" => (x_ge_y, flag_clear, flag_set),
+            "<=" => (y_ge_x, flag_set, flag_clear),
+            ">" => (y_ge_x, flag_clear, flag_set),
+            _ => return Err(format!("Unknown comparison \"{}\".", operator)),
+        };
+        Ok(Condition {
+            setup,
+            skip_if_false,
+            skip_if_true,
+        })
+    }
+
+    fn emit_condition(&mut self, condition: &Condition, skip: u16) -> Result<()> {
+        for &op in &condition.setup {
+            self.emit(op)?;
+        }
+        self.emit(skip)
+    }
+
+    fn expand(&mut self, name: &str) -> Result<()> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(format!("Too many macro expansions in \"{}\".", name));
+        }
+
+        let params = self.macros[name].params.clone();
+        let mut args = HashMap::new();
+        for param in params {
+            let arg = self.next()?;
+            args.insert(param, arg);
+        }
+
+        let body = &self.macros[name].body;
+        for token in body.iter().rev() {
+            let text = args.get(&token.text).unwrap_or(&token.text);
+            self.tokens.push_front(Token {
+                text: text.clone(),
+                line: token.line,
+            });
+        }
+        Ok(())
+    }
+
+    // Reads a `{ ... }` block, minus the braces
+    fn block(&mut self) -> Result<Vec<Token>> {
+        self.expect("{")?;
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self
+                .tokens
+                .pop_front()
+                .ok_or("Missing \"}\" at the end of the file.")?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => return Ok(tokens),
+                "}" => depth -= 1,
+                _ => (),
+            }
+            tokens.push(token);
+        }
+    }
+
+    fn calc(&self, expression: &[Token]) -> Result<f64> {
+        let lookup = |name: &str| match name {
+            "HERE" => Some(self.here as f64),
+            _ => self
+                .constants
+                .get(name)
+                .copied()
+                .or_else(|| self.labels.get(name).map(|&address| address as f64)),
+        };
+        calc::evaluate(expression, &lookup)
+    }
+
+    fn next(&mut self) -> Result<String> {
+        let token = self
+            .tokens
+            .pop_front()
+            .ok_or("Unexpected end of the file.")?;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("Expected \"{}\", found \"{}\".", expected, token)),
+        }
+    }
+
+    fn lookup_register(&self, name: &str) -> Option<u8> {
+        self.aliases
+            .get(name)
+            .copied()
+            .or_else(|| parse_register(name))
+    }
+
+    fn register(&mut self) -> Result<u8> {
+        let name = self.next()?;
+        self.lookup_register(&name)
+            .ok_or_else(|| format!("Expected a register, found \"{}\".", name))
+    }
+
+    // Numbers, constants and labels defined so far
+    fn value(&self, name: &str) -> Option<i64> {
+        parse_number(name)
+            .or_else(|| self.constants.get(name).map(|&value| value as i64))
+            .or_else(|| self.labels.get(name).map(|&address| address as i64))
+    }
+
+    fn byte(&self, name: &str) -> Result<u8> {
+        let value = self
+            .value(name)
+            .ok_or_else(|| format!("Expected a number, found \"{}\".", name))?;
+        check_byte(value)
+    }
+
+    fn register_op(&mut self, op: u16) -> Result<()> {
+        let x = self.register()? as u16;
+        self.emit(op | (x << 8))
+    }
+
+    // XO-CHIP adds `save vx - vy` and `load vx - vy`
+    fn register_range_op(&mut self, op: u16) -> Result<()> {
+        self.register_op(op)?;
+        if self.tokens.front().is_some_and(|token| token.text == "-") {
+            return Err("Register ranges need XO-CHIP.".to_string());
+        }
+        Ok(())
+    }
+
+    // An instruction taking a 12-bit address, which may be a label defined later on
+    fn address_op(&mut self, op: u16) -> Result<()> {
+        let target = self.next()?;
+        match self.value(&target) {
+            Some(address) => {
+                let address = check_address(address)?;
+                self.emit(op | address)
+            }
+            None if UNSUPPORTED.contains(&target.as_str()) => {
+                Err(format!("\"{}\" needs SUPER-CHIP or XO-CHIP.", target))
+            }
+            None => {
+                self.fixup(&target, FixupKind::Address);
+                self.emit(op)
+            }
+        }
+    }
+
+    fn call(&mut self, label: &str) -> Result<()> {
+        match self.labels.get(label) {
+            Some(&address) => self.emit(0x2000 | address as u16),
+            None => {
+                self.fixup(label, FixupKind::Address);
+                self.emit(0x2000)
+            }
+        }
+    }
+
+    fn fixup(&mut self, label: &str, kind: FixupKind) {
+        self.fixups.push(Fixup {
+            at: self.here,
+            label: label.to_string(),
+            line: self.line,
+            kind,
+        });
+    }
+
+    fn patch_jump(&mut self, at: usize, target: usize) {
+        let at = at - START_ADDR;
+        self.rom[at] = 0x10 | (target >> 8) as u8;
+        self.rom[at + 1] = target as u8;
+    }
+
+    fn emit(&mut self, op: u16) -> Result<()> {
+        self.write((op >> 8) as u8)?;
+        self.write(op as u8)
+    }
+
+    fn write(&mut self, byte: u8) -> Result<()> {
+        if self.here >= RAM_SIZE {
+            return Err("The program doesn't fit in memory.".to_string());
+        }
+        let at = self.here - START_ADDR;
+        if at >= self.rom.len() {
+            self.rom.resize(at + 1, 0);
+        }
+        self.rom[at] = byte;
+        self.here += 1;
+        Ok(())
+    }
+}
+
+// Bytes may be written as signed or unsigned
+fn check_byte(value: i64) -> Result<u8> {
+    match value {
+        -128..=255 => Ok(value as u8),
+        _ => Err(format!("{} doesn't fit in a byte.", value)),
+    }
+}
+
+fn check_address(value: i64) -> Result<u16> {
+    match value {
+        0..=0xFFF => Ok(value as u16),
+        _ => Err(format!("{} isn't a valid address.", value)),
+    }
+}
@@ -0,0 +1,40 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: cargo run -- <source.8o> [output.ch8]";
+
+fn main() -> ExitCode {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let (source, output) = match args.as_slice() {
+        [source] => (source, Path::new(source).with_extension("ch8")),
+        [source, output] => (source, output.into()),
+        _ => {
+            println!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let program = match fs::read_to_string(source) {
+        Ok(program) => program,
+        Err(error) => {
+            println!("Unable to read {}: {}", source, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let rom = match assembler::assemble(&program) {
+        Ok(rom) => rom,
+        Err(error) => {
+            println!("{}:{}", source, error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(error) = fs::write(&output, &rom) {
+        println!("Unable to write {}: {}", output.display(), error);
+        return ExitCode::FAILURE;
+    }
+    println!("{} bytes written to {}", rom.len(), output.display());
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,301 @@
+//! Golden outputs of the assembler: the ROM each construct assembles to, and the error each
+//! mistake is reported with.
+
+use assembler::{assemble, Error};
+
+fn rom(source: &str) -> Vec<u8> {
+    assemble(source).unwrap_or_else(|error| panic!("{} in {:?}", error, source))
+}
+
+fn error(source: &str) -> (usize, String) {
+    match assemble(source) {
+        Ok(rom) => panic!("{:?} assembled to {:02X?}", source, rom),
+        Err(Error { line, message }) => (line, message),
+    }
+}
+
+fn at(line: usize, message: &str) -> (usize, String) {
+    (line, message.to_string())
+}
+
+#[test]
+fn main_first_needs_no_jump() {
+    assert_eq!(rom(": main clear"), [0x00, 0xE0]);
+}
+
+#[test]
+fn jumps_to_main_and_calls_subroutines() {
+    assert_eq!(
+        rom(": sub return : main sub later ; : later ;"),
+        [0x12, 0x04, 0x00, 0xEE, 0x22, 0x02, 0x22, 0x0A, 0x00, 0xEE, 0x00, 0xEE]
+    );
+}
+
+#[test]
+fn register_statements() {
+    let source = "
+        : main
+        v0 := 5  v1 := v0  v2 += 3  v2 += v1  v3 -= 1  v3 -= v1  v4 =- v5
+        v6 |= v7  v8 &= v9  va ^= vb  vc >>= vd  ve <<= vf
+        v0 := random 0x0F  v1 := key  v2 := delay  delay := v3  buzzer := v4
+    ";
+    #[rustfmt::skip]
+    let expected = [
+        0x60, 0x05, 0x81, 0x00, 0x72, 0x03, 0x82, 0x14, 0x73, 0xFF, 0x83, 0x15, 0x84, 0x57,
+        0x86, 0x71, 0x88, 0x92, 0x8A, 0xB3, 0x8C, 0xD6, 0x8E, 0xFE,
+        0xC0, 0x0F, 0xF1, 0x0A, 0xF2, 0x07, 0xF3, 0x15, 0xF4, 0x18,
+    ];
+    assert_eq!(rom(source), expected);
+}
+
+#[test]
+fn memory_and_display_statements() {
+    let source = "
+        : main
+        i := 0x300  i := hex v1  i += v2  bcd v3  save v4  load v5  sprite v0 v1 5
+        jump0 0x210  native 0x123  jump main
+    ";
+    #[rustfmt::skip]
+    let expected = [
+        0xA3, 0x00, 0xF1, 0x29, 0xF2, 0x1E, 0xF3, 0x33, 0xF4, 0x55, 0xF5, 0x65, 0xD0, 0x15,
+        0xB2, 0x10, 0x01, 0x23, 0x12, 0x00,
+    ];
+    assert_eq!(rom(source), expected);
+}
+
+#[test]
+fn const_and_alias() {
+    assert_eq!(
+        rom(":const SPEED 7 :alias speed v3 : main speed := SPEED speed += speed"),
+        [0x12, 0x02, 0x63, 0x07, 0x83, 0x34]
+    );
+}
+
+#[test]
+fn calc_evaluates_right_to_left() {
+    assert_eq!(
+        rom(":calc X { 2 * 3 + 1 } :calc Y { ( 2 * 3 ) + 1 } : main v0 := X v1 := Y"),
+        [0x12, 0x02, 0x60, 0x08, 0x61, 0x07]
+    );
+}
+
+#[test]
+fn macros_substitute_their_arguments() {
+    assert_eq!(
+        rom(":macro twice reg n { reg += n reg += n } : main twice v2 3 twice v5 1"),
+        [0x12, 0x02, 0x72, 0x03, 0x72, 0x03, 0x75, 0x01, 0x75, 0x01]
+    );
+}
+
+#[test]
+fn bytes_and_bare_numbers() {
+    assert_eq!(
+        rom(": main :byte 0xAB :byte { 1 + 2 } :byte -1 0b1010 255"),
+        [0xAB, 0x03, 0xFF, 0x0A, 0xFF]
+    );
+}
+
+#[test]
+fn org_moves_the_output() {
+    let rom = rom(": main jump data :org 0x300 : data 0x42");
+    assert_eq!(rom.len(), 0x101);
+    assert_eq!(rom[..2], [0x13, 0x00]);
+    assert!(rom[2..0x100].iter().all(|&byte| byte == 0));
+    assert_eq!(rom[0x100], 0x42);
+}
+
+#[test]
+fn unpack_splits_addresses() {
+    assert_eq!(
+        rom(": main :unpack 0xA data : data 0x12"),
+        [0x60, 0xA2, 0x61, 0x04, 0x12]
+    );
+    assert_eq!(
+        rom(": data 0x12 : main :unpack 1 data"),
+        [0x12, 0x03, 0x12, 0x60, 0x12, 0x61, 0x02]
+    );
+}
+
+#[test]
+fn debugger_hints_are_skipped() {
+    assert_eq!(
+        rom(": main :breakpoint here :monitor v0 1 clear"),
+        [0x00, 0xE0]
+    );
+}
+
+#[test]
+fn loops() {
+    assert_eq!(
+        rom(": main loop v0 += 1 while v0 != 10 again"),
+        [0x70, 0x01, 0x40, 0x0A, 0x12, 0x08, 0x12, 0x00]
+    );
+}
+
+#[test]
+fn if_then() {
+    assert_eq!(
+        rom(": main if v0 == 1 then v1 := 2 if v0 != v3 then clear"),
+        [0x40, 0x01, 0x61, 0x02, 0x50, 0x30, 0x00, 0xE0]
+    );
+    assert_eq!(
+        rom(": main if v3 key then clear if v3 -key then clear"),
+        [0xE3, 0xA1, 0x00, 0xE0, 0xE3, 0x9E, 0x00, 0xE0]
+    );
+}
+
+#[test]
+fn comparisons_test_the_borrow_flag() {
+    // >= and < subtract VY from VX, <= and > VX from VY
+    assert_eq!(
+        rom(": main if v1 >= v2 then clear"),
+        [0x8F, 0x10, 0x8F, 0x25, 0x3F, 0x00, 0x00, 0xE0]
+    );
+    assert_eq!(
+        rom(": main if v1 < 5 then clear"),
+        [0x6F, 0x05, 0x8F, 0x17, 0x4F, 0x00, 0x00, 0xE0]
+    );
+}
+
+#[test]
+fn if_begin_else_end() {
+    assert_eq!(
+        rom(": main if v0 > v1 begin v2 := 1 else v2 := 2 end"),
+        [0x8F, 0x00, 0x8F, 0x17, 0x3F, 0x00, 0x12, 0x0C, 0x62, 0x01, 0x12, 0x0E, 0x62, 0x02]
+    );
+    assert_eq!(
+        rom(": main if v0 == 0 begin clear end"),
+        [0x30, 0x00, 0x12, 0x06, 0x00, 0xE0]
+    );
+}
+
+#[test]
+fn labels_and_flow_errors() {
+    assert_eq!(error(": main\nfoo"), at(2, "Undefined label \"foo\"."));
+    assert_eq!(
+        error(": main\n: main"),
+        at(2, "Label \"main\" is already defined.")
+    );
+    assert_eq!(
+        error(": main\nloop\nclear"),
+        at(2, "This loop is missing its \"again\".")
+    );
+    assert_eq!(
+        error(": main\nif v0 == 1 begin\nclear"),
+        at(2, "This \"begin\" is missing its \"end\".")
+    );
+    assert_eq!(error(": main again"), at(1, "\"again\" without a loop."));
+    assert_eq!(error(": main end"), at(1, "\"end\" without a \"begin\"."));
+    assert_eq!(error(": main else"), at(1, "\"else\" without a \"begin\"."));
+    assert_eq!(
+        error(": main if v0 == 1 begin else else end"),
+        at(1, "A \"begin\" can only have one \"else\".")
+    );
+    assert_eq!(
+        error(": main while v0 == 1"),
+        at(1, "\"while\" outside of a loop.")
+    );
+    assert_eq!(
+        error(": main if v0 == 1 clear"),
+        at(1, "Expected \"then\" or \"begin\", found \"clear\".")
+    );
+    assert_eq!(error(": main then"), at(1, "Unexpected \"then\"."));
+}
+
+#[test]
+fn operand_errors() {
+    assert_eq!(
+        error(": main v0 := 256"),
+        at(1, "256 doesn't fit in a byte.")
+    );
+    assert_eq!(
+        error(": main jump 0x1000"),
+        at(1, "4096 isn't a valid address.")
+    );
+    assert_eq!(
+        error(": main sprite v0 v1 16"),
+        at(1, "Invalid sprite height \"16\".")
+    );
+    assert_eq!(
+        error(": main bcd 5"),
+        at(1, "Expected a register, found \"5\".")
+    );
+    assert_eq!(
+        error(": main v0 |= 1"),
+        at(1, "Expected a register, found \"1\".")
+    );
+    assert_eq!(
+        error(": main v0 := foo"),
+        at(1, "Expected a number, found \"foo\".")
+    );
+    assert_eq!(error(": main v0 ** 1"), at(1, "Unknown operator \"**\"."));
+    assert_eq!(
+        error(": main i -= v0"),
+        at(1, "Unknown operator \"-=\" for i.")
+    );
+    assert_eq!(
+        error(": main if v0 ~ 1 then"),
+        at(1, "Unknown comparison \"~\".")
+    );
+    assert_eq!(error(": main v0 :="), at(1, "Unexpected end of the file."));
+    assert_eq!(
+        error(": main delay v0"),
+        at(1, "Expected \":=\", found \"v0\".")
+    );
+}
+
+#[test]
+fn directive_errors() {
+    assert_eq!(error(":foo"), at(1, "Unknown directive \":foo\"."));
+    assert_eq!(
+        error(":const X foo"),
+        at(1, "Expected a number, found \"foo\".")
+    );
+    assert_eq!(
+        error(":alias x 5"),
+        at(1, "Expected a register, found \"5\".")
+    );
+    assert_eq!(
+        error(":calc X { 1\n+ 2"),
+        at(1, "Missing \"}\" at the end of the file.")
+    );
+    assert_eq!(error(":org 0x100"), at(1, "Invalid address \"0x100\"."));
+    assert_eq!(
+        error(":unpack 0x10 x"),
+        at(1, "Expected a nybble, found \"0x10\".")
+    );
+    assert_eq!(
+        error(": main :byte 300"),
+        at(1, "300 doesn't fit in a byte.")
+    );
+    assert_eq!(
+        error(":macro m { m } : main m"),
+        at(1, "Too many macro expansions in \"m\".")
+    );
+    assert_eq!(
+        error(": main\n:org 0xFFF\nclear"),
+        at(3, "The program doesn't fit in memory.")
+    );
+}
+
+#[test]
+fn unsupported_instructions() {
+    assert_eq!(
+        error(": main hires"),
+        at(1, "\"hires\" needs SUPER-CHIP or XO-CHIP.")
+    );
+    assert_eq!(
+        error(": main i := long 0x1000"),
+        at(1, "\"long\" needs SUPER-CHIP or XO-CHIP.")
+    );
+    assert_eq!(
+        error(": main save v0 - v3"),
+        at(1, "Register ranges need XO-CHIP.")
+    );
+}
+
+#[test]
+fn errors_print_their_line() {
+    let error = assemble(": main\n\nclear\nv0 := 256").unwrap_err();
+    assert_eq!(error.to_string(), "line 4: 256 doesn't fit in a byte.");
+}
@@ -0,0 +1,79 @@
+//! Instruction dispatch throughput, for batch runs doing thousands of instructions per frame.
+//!
+//! `cargo bench` runs a busy loop of arithmetic, skips, calls and BCD stores, drawing a sprite
+//! now and then, and reports the time per instruction of the interpreter and the recompiler. The
+//! interpreter without its decode cache is measured by
+//! `cargo bench --no-default-features --features std,rand`.
+
+#[cfg(feature = "decode-cache")]
+use core::recompiler::Recompiler;
+use core::Emulator;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+const TICKS: u32 = 20_000_000;
+const RUNS: u32 = 5;
+
+#[rustfmt::skip]
+const ROM: [u8; 36] = [
+    0x60, 0x00, // 200: LD V0, 0x00
+    0x61, 0x00, // 202: LD V1, 0x00
+    0xA3, 0x00, // 204: LDI 0x300
+    0x70, 0x01, // 206: ADDIW V0, 0x01
+    0x81, 0x04, // 208: ADD V1, V0
+    0x82, 0x16, // 20A: SHR V2
+    0x83, 0x13, // 20C: XOR V3, V1
+    0x33, 0x80, // 20E: SEQ V3, 0x80
+    0x22, 0x1A, // 210: CALL 0x21A
+    0x40, 0x00, // 212: SNQ V0, 0x00
+    0xD0, 0x15, // 214: DRAW V0, V1, 5
+    0x12, 0x06, // 216: JMP 0x206
+    0x00, 0x00, // 218: padding
+    0x84, 0x10, // 21A: MV V4, V1
+    0x84, 0x02, // 21C: AND V4, V0
+    0xF4, 0x33, // 21E: SBCD V4
+    0x84, 0x45, // 220: SUB V4, V4
+    0x00, 0xEE, // 222: RET
+];
+
+fn main() {
+    #[cfg(feature = "decode-cache")]
+    let interpreter = "interpreter (decode cache)";
+    #[cfg(not(feature = "decode-cache"))]
+    let interpreter = "interpreter (no decode cache)";
+    report(interpreter, |emu| {
+        for _ in 0..TICKS {
+            emu.tick();
+        }
+    });
+
+    #[cfg(feature = "decode-cache")]
+    {
+        let mut recompiler = Recompiler::new();
+        report("recompiler", |emu| recompiler.run(emu, TICKS as usize));
+    }
+}
+
+// Best time per instruction over a few runs of the ROM
+fn report(backend: &str, mut run: impl FnMut(&mut Emulator)) {
+    let mut best = f64::MAX;
+    for _ in 0..RUNS {
+        let mut emu = Emulator::new();
+        emu.load(&ROM);
+
+        let start = Instant::now();
+        run(&mut emu);
+        black_box(emu.get_display());
+
+        let nanos = start.elapsed().as_nanos() as f64 / TICKS as f64;
+        best = best.min(nanos);
+    }
+
+    println!(
+        "{}: {:.2} ns per instruction ({:.0} million instructions per second)",
+        backend,
+        best,
+        1000.0 / best
+    );
+}
@@ -0,0 +1,318 @@
+//! Static analysis of ROMs: a disassembler and a control-flow graph.
+//!
+//! The graph is built by following every path from 0x200: jumps, calls, returns and both
+//! outcomes of the skip instructions. Whatever is never reached is taken to be data, such as
+//! sprites. `JMP2` jumps to an address computed at runtime, so its targets can't be known and
+//! the walk stops there; these jumps are listed in `Cfg::unresolved`.
+
+use crate::*;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+/// How control gets from a block to one of its successors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// Falling through to the next instruction, including when a skip isn't taken.
+    Next,
+    /// `JMP` to the successor.
+    Jump,
+    /// A skip instruction jumping over the next instruction.
+    Skip,
+}
+
+/// A run of instructions executed one after the other, only entered through its first one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub start: u16,
+    /// Address just past the last instruction.
+    pub end: u16,
+    pub successors: Vec<(u16, Edge)>,
+    /// Subroutines called from the block, which return to the instruction after the `CALL`.
+    pub calls: Vec<u16>,
+}
+
+/// Control-flow graph of a ROM.
+#[derive(Clone, Debug, Default)]
+pub struct Cfg {
+    pub blocks: BTreeMap<u16, Block>,
+    /// Entry points of the subroutines, i.e. the targets of `CALL`.
+    pub subroutines: BTreeSet<u16>,
+    /// Addresses of the `JMP2` instructions, whose targets aren't known.
+    pub unresolved: Vec<u16>,
+    /// Addresses of opcodes the emulator doesn't know, where the walk stopped.
+    pub invalid: Vec<u16>,
+    code: BTreeSet<u16>,
+}
+
+enum Flow {
+    Continue,
+    Call(u16),
+    Jump(u16),
+    Skip,
+    Stop,
+}
+
+impl Cfg {
+    /// Builds the graph of a ROM, as loaded at 0x200 by `Emulator::load`.
+    pub fn new(rom: &[u8]) -> Cfg {
+        let mut cfg = Cfg::default();
+        let mut leaders = BTreeSet::from([START_ADDR]);
+        let mut pending = vec![START_ADDR];
+
+        while let Some(mut pc) = pending.pop() {
+            while let Some(op) = fetch(rom, pc) {
+                if !cfg.code.insert(pc) {
+                    break;
+                }
+
+                // In usize, so instructions at the end of RAM can't wrap around
+                let next = pc as usize + 2;
+                match flow(op) {
+                    Flow::Continue => (),
+                    Flow::Call(target) => {
+                        cfg.subroutines.insert(target);
+                        leaders.insert(target);
+                        pending.push(target);
+                    }
+                    Flow::Jump(target) => {
+                        leaders.insert(target);
+                        pending.push(target);
+                        break;
+                    }
+                    Flow::Skip => {
+                        for target in skip_targets(pc) {
+                            leaders.insert(target);
+                            pending.push(target);
+                        }
+                        break;
+                    }
+                    Flow::Stop => {
+                        match decode(op) {
+                            Ok(Instruction::Jmp2(_)) => cfg.unresolved.push(pc),
+                            Err(_) => cfg.invalid.push(pc),
+                            Ok(_) => (),
+                        }
+                        break;
+                    }
+                }
+                if next >= RAM_SIZE {
+                    break;
+                }
+                pc = next as u16;
+            }
+        }
+        cfg.unresolved.sort_unstable();
+        cfg.invalid.sort_unstable();
+
+        // Split the instructions into blocks, at jump targets and after any change of flow
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut open = false;
+        for &pc in &cfg.code {
+            let contiguous = open && blocks.last().is_some_and(|block| block.end == pc);
+            if !contiguous || leaders.contains(&pc) {
+                if let Some(previous) = blocks.last_mut().filter(|_| contiguous) {
+                    previous.successors.push((pc, Edge::Next));
+                }
+                blocks.push(Block {
+                    start: pc,
+                    end: pc,
+                    successors: Vec::new(),
+                    calls: Vec::new(),
+                });
+            }
+
+            let block = blocks.last_mut().unwrap();
+            block.end = pc + 2;
+            open = false;
+            match flow(fetch(rom, pc).unwrap_or_default()) {
+                Flow::Continue => open = true,
+                Flow::Call(target) => {
+                    block.calls.push(target);
+                    open = true;
+                }
+                Flow::Jump(target) => block.successors.push((target, Edge::Jump)),
+                Flow::Skip => {
+                    let edges = [Edge::Next, Edge::Skip];
+                    block.successors.extend(skip_targets(pc).zip(edges));
+                }
+                Flow::Stop => (),
+            }
+        }
+        cfg.blocks = blocks
+            .into_iter()
+            .map(|block| (block.start, block))
+            .collect();
+
+        cfg
+    }
+
+    /// Whether the byte at `address` is part of a reachable instruction.
+    pub fn is_code(&self, address: u16) -> bool {
+        self.code.contains(&address) || address > 0 && self.code.contains(&(address - 1))
+    }
+
+    /// Addresses of the reachable instructions, in order.
+    pub fn instructions(&self) -> impl Iterator<Item = u16> + '_ {
+        self.code.iter().copied()
+    }
+
+    /// Disassembles the ROM, listing reachable instructions as such and anything else as data.
+    pub fn listing(&self, rom: &[u8]) -> String {
+        self.annotated_listing(rom, |_| false, |_, _| String::new())
+    }
+
+    // `also_code` adds instructions the analysis couldn't find, e.g. ones seen running, and
+    // `annotate` prefixes each line given its address and whether it's an instruction
+    pub(crate) fn annotated_listing(
+        &self,
+        rom: &[u8],
+        also_code: impl Fn(u16) -> bool,
+        annotate: impl Fn(u16, bool) -> String,
+    ) -> String {
+        let mut out = String::new();
+        let end = START_ADDR as usize + rom.len();
+        let mut address = START_ADDR;
+        while (address as usize) < end {
+            if self.subroutines.contains(&address) {
+                let _ = writeln!(out, "\nsub_{:03X}:", address);
+            } else if self.blocks.contains_key(&address) {
+                let _ = writeln!(out, "loc_{:03X}:", address);
+            }
+
+            let is_code = self.code.contains(&address) || also_code(address);
+            let prefix = annotate(address, is_code);
+            match fetch(rom, address).filter(|_| is_code) {
+                Some(op) => {
+                    let _ = writeln!(
+                        out,
+                        "{}  {:03X}  {:04X}  {}",
+                        prefix,
+                        address,
+                        op,
+                        disassemble(op)
+                    );
+                    address += 2;
+                }
+                None => {
+                    let byte = rom[(address - START_ADDR) as usize];
+                    let _ = writeln!(
+                        out,
+                        "{}  {:03X}  {:02X}    DB 0x{:02X}",
+                        prefix, address, byte, byte
+                    );
+                    address += 1;
+                }
+            }
+        }
+        out
+    }
+
+    /// Renders the graph in Graphviz's DOT language, with each block's disassembly. Calls are
+    /// dashed edges, subroutine entries have a double border and unresolved jumps are red.
+    pub fn to_dot(&self, rom: &[u8]) -> String {
+        let mut out = String::from("digraph cfg {\n  node [shape=box, fontname=monospace];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for pc in (block.start..block.end).step_by(2) {
+                let op = fetch(rom, pc).unwrap_or_default();
+                let _ = write!(label, "{:03X}  {}\\l", pc, disassemble(op));
+            }
+
+            let mut style = String::new();
+            if self.subroutines.contains(&block.start) {
+                style.push_str(", peripheries=2");
+            }
+            if self.unresolved.contains(&(block.end - 2)) {
+                style.push_str(", color=red");
+            }
+            let _ = writeln!(
+                out,
+                "  b{:03X} [label=\"{}\"{}];",
+                block.start, label, style
+            );
+
+            for (target, edge) in &block.successors {
+                let attributes = match edge {
+                    Edge::Next => "",
+                    Edge::Jump => " [label=\"jmp\"]",
+                    Edge::Skip => " [label=\"skip\"]",
+                };
+                let _ = writeln!(
+                    out,
+                    "  b{:03X} -> b{:03X}{};",
+                    block.start, target, attributes
+                );
+            }
+            for target in &block.calls {
+                let _ = writeln!(
+                    out,
+                    "  b{:03X} -> b{:03X} [style=dashed, label=\"call\"];",
+                    block.start, target
+                );
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn fetch(rom: &[u8], address: u16) -> Option<u16> {
+    let at = (address as usize).checked_sub(START_ADDR as usize)?;
+    let bytes = rom.get(at..at + 2)?;
+    Some(((bytes[0] as u16) << 8) | bytes[1] as u16)
+}
+
+// The instructions after a skip at pc, when it isn't taken and when it is, if they're in RAM
+fn skip_targets(pc: u16) -> impl Iterator<Item = u16> {
+    [pc as usize + 2, pc as usize + 4]
+        .into_iter()
+        .filter(|&target| target < RAM_SIZE)
+        .map(|target| target as u16)
+}
+
+fn flow(op: u16) -> Flow {
+    match decode(op) {
+        Ok(Instruction::Ret) => Flow::Stop,
+        Ok(Instruction::Jmp(address)) => Flow::Jump(address),
+        Ok(Instruction::Call(address)) => Flow::Call(address),
+        Ok(
+            Instruction::Seq(..)
+            | Instruction::Snq(..)
+            | Instruction::Seqr(..)
+            | Instruction::Snqr(..)
+            | Instruction::Skp(_)
+            | Instruction::Snp(_),
+        ) => Flow::Skip,
+        Ok(Instruction::Jmp2(_)) | Err(_) => Flow::Stop,
+        Ok(_) => Flow::Continue,
+    }
+}
+
+// Memory accessed through I by an instruction: sprites, register loads and stores, and BCD
+pub(crate) enum Access {
+    Read(Range<usize>),
+    Write(Range<usize>),
+}
+
+pub(crate) fn memory_access(op: u16, i: u16) -> Option<Access> {
+    let i = i as usize;
+    let bytes = |length: usize| i.min(RAM_SIZE)..(i + length).min(RAM_SIZE);
+    match decode(op).ok()? {
+        Instruction::Draw(_, _, height) => Some(Access::Read(bytes(height as usize))),
+        Instruction::Ldr(x) => Some(Access::Read(bytes(x as usize + 1))),
+        Instruction::Strr(x) => Some(Access::Write(bytes(x as usize + 1))),
+        Instruction::Sbcd(_) => Some(Access::Write(bytes(3))),
+        _ => None,
+    }
+}
+
+/// Disassembles an opcode, using the mnemonics of the emulator's source, e.g. `SEQ V3, 0x12`.
+/// Unknown opcodes come out as raw words, `DW 0x0123`.
+pub fn disassemble(op: u16) -> String {
+    match decode(op) {
+        Ok(instruction) => instruction.to_string(),
+        Err(_) => format!("DW 0x{:04X}", op),
+    }
+}
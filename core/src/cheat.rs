@@ -0,0 +1,99 @@
+//! RAM search and freezing, the building blocks of cheat codes.
+//!
+//! A search starts with every address as a candidate. Each `filter` call compares the RAM with
+//! the previous call and drops the addresses that don't match, e.g. lose a life, filter by
+//! `Decreased`, and the lives counter is soon one of the few candidates left.
+
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+    EqualTo(u8),
+}
+
+pub struct RamSearch {
+    snapshot: [u8; RAM_SIZE],
+    candidates: [bool; RAM_SIZE],
+}
+
+impl RamSearch {
+    /// Starts a search with every address as a candidate.
+    pub fn new(emu: &Emulator) -> Self {
+        Self {
+            snapshot: emu.ram,
+            candidates: [true; RAM_SIZE],
+        }
+    }
+
+    /// Keeps the candidates whose value compares to the last snapshot as asked, then takes a
+    /// new snapshot.
+    pub fn filter(&mut self, emu: &Emulator, comparison: Comparison) {
+        for (address, candidate) in self.candidates.iter_mut().enumerate() {
+            let (old, new) = (self.snapshot[address], emu.ram[address]);
+            *candidate &= match comparison {
+                Comparison::Unchanged => new == old,
+                Comparison::Changed => new != old,
+                Comparison::Increased => new > old,
+                Comparison::Decreased => new < old,
+                Comparison::EqualTo(value) => new == value,
+            };
+        }
+        self.snapshot = emu.ram;
+    }
+
+    pub fn candidates(&self) -> impl Iterator<Item = usize> + '_ {
+        self.candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, &candidate)| candidate)
+            .map(|(address, _)| address)
+    }
+
+    pub fn count(&self) -> usize {
+        self.candidates().count()
+    }
+}
+
+/// RAM bytes pinned to a value, rewritten by `apply` every frame.
+pub struct Freezer {
+    frozen: [Option<u8>; RAM_SIZE],
+}
+
+impl Freezer {
+    pub fn new() -> Self {
+        Self {
+            frozen: [None; RAM_SIZE],
+        }
+    }
+
+    pub fn freeze(&mut self, address: usize, value: u8) {
+        self.frozen[address] = Some(value);
+    }
+
+    pub fn unfreeze(&mut self, address: usize) {
+        self.frozen[address] = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.frozen = [None; RAM_SIZE];
+    }
+
+    /// Writes the frozen values back to the RAM. Call it once per frame, after the ticks.
+    pub fn apply(&self, emu: &mut Emulator) {
+        for (address, value) in self.frozen.iter().enumerate() {
+            if let Some(value) = value {
+                emu.write_memory(address, &[*value]);
+            }
+        }
+    }
+}
+
+impl Default for Freezer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
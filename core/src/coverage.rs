@@ -0,0 +1,116 @@
+//! Code coverage: which addresses ran as instructions and which were read or written as data.
+//!
+//! Like the profiler, `record` is called before every `Emulator::tick`. Data accesses are those
+//! of `DRAW` (sprites), `LDR`, `STRR` and `SBCD`, the only instructions touching memory through I.
+
+use crate::analysis::{memory_access, Access, Cfg};
+use crate::*;
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::ops::Range;
+
+const EXECUTED: u8 = 1 << 0;
+const STARTS_INSTRUCTION: u8 = 1 << 1;
+const READ: u8 = 1 << 2;
+const WRITTEN: u8 = 1 << 3;
+
+pub struct Coverage {
+    flags: Vec<u8>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self {
+            flags: vec![0; RAM_SIZE],
+        }
+    }
+
+    /// Marks the instruction the emulator is about to run, and the memory it accesses.
+    pub fn record(&mut self, emu: &Emulator) {
+        let pc = emu.pc as usize;
+        if pc + 1 >= RAM_SIZE {
+            return;
+        }
+        self.flags[pc] |= EXECUTED | STARTS_INSTRUCTION;
+        self.flags[pc + 1] |= EXECUTED;
+
+        let op = ((emu.ram[pc] as u16) << 8) | emu.ram[pc + 1] as u16;
+        match memory_access(op, emu.i_reg) {
+            Some(Access::Read(range)) => self.mark(range, READ),
+            Some(Access::Write(range)) => self.mark(range, WRITTEN),
+            None => (),
+        }
+    }
+
+    fn mark(&mut self, range: Range<usize>, flag: u8) {
+        for address in range {
+            self.flags[address] |= flag;
+        }
+    }
+
+    /// Whether the byte at `address` ran as part of an instruction.
+    pub fn is_executed(&self, address: usize) -> bool {
+        self.flags[address] & EXECUTED != 0
+    }
+
+    pub fn is_read(&self, address: usize) -> bool {
+        self.flags[address] & READ != 0
+    }
+
+    pub fn is_written(&self, address: usize) -> bool {
+        self.flags[address] & WRITTEN != 0
+    }
+
+    /// Disassembles the ROM, marking the instructions that never ran with `#####` and the data
+    /// with how it was accessed: `R` for read, `W` for written, `-` for neither. A summary
+    /// follows. Code is found by the static analysis, plus any instruction that ran.
+    pub fn annotated_listing(&self, rom: &[u8]) -> String {
+        let cfg = Cfg::new(rom);
+        let starts = |address: u16| self.flags[address as usize] & STARTS_INSTRUCTION != 0;
+        let mut out = cfg.annotated_listing(rom, starts, |address, is_code| {
+            let address = address as usize;
+            let mark = match (is_code, self.is_read(address), self.is_written(address)) {
+                (true, _, _) if self.is_executed(address) => "",
+                (true, _, _) => "#####",
+                (false, true, true) => "RW",
+                (false, true, false) => "R",
+                (false, false, true) => "W",
+                (false, false, false) => "-",
+            };
+            format!("{:>5}", mark)
+        });
+
+        let end = START_ADDR + rom.len() as u16;
+        let instructions: BTreeSet<u16> = cfg
+            .instructions()
+            .chain((START_ADDR..end).filter(|&address| starts(address)))
+            .collect();
+        let executed = instructions
+            .iter()
+            .filter(|&&address| self.is_executed(address as usize))
+            .count();
+        let read = (0..RAM_SIZE)
+            .filter(|&address| self.is_read(address))
+            .count();
+        let written = (0..RAM_SIZE)
+            .filter(|&address| self.is_written(address))
+            .count();
+        let _ = writeln!(
+            out,
+            "\n{} of {} instructions executed ({:.1}%), {} bytes read, {} bytes written",
+            executed,
+            instructions.len(),
+            100.0 * executed as f64 / instructions.len().max(1) as f64,
+            read,
+            written
+        );
+        out
+    }
+}
@@ -0,0 +1,151 @@
+//! A gym-style environment for training agents on CHIP-8 games.
+//!
+//! Each `step` holds down the keys of an action and runs a few frames, without a window, through
+//! the recompiler. Games are described by a `Game`: the ROM, how fast it runs, which key
+//! combinations make up the actions, and the reward and termination functions, which look at the
+//! RAM. `Env` owns everything it runs, so instances can be moved to as many threads as needed.
+
+use crate::recompiler::Recompiler;
+use crate::*;
+
+/// Reward of a step, given the RAM before and after it.
+pub type Reward = Box<dyn Fn(&[u8], &[u8]) -> f64 + Send + Sync>;
+/// Whether the episode is over, given the RAM.
+pub type Done = Box<dyn Fn(&[u8]) -> bool + Send + Sync>;
+
+pub struct Game {
+    pub rom: Vec<u8>,
+    pub quirks: Quirks,
+    pub ticks_per_frame: usize,
+    /// The keys held by each action, one bit per key: bit 5 is key 5. Defaults to doing nothing
+    /// and each key on its own.
+    pub actions: Vec<u16>,
+    pub reward: Reward,
+    pub done: Done,
+}
+
+impl Game {
+    /// A game run at the frontend's default speed, never ending and giving no reward until
+    /// `reward` and `done` are set.
+    pub fn new(rom: &[u8]) -> Self {
+        Self {
+            rom: rom.to_vec(),
+            quirks: Quirks::default(),
+            ticks_per_frame: 10,
+            actions: std::iter::once(0)
+                .chain((0..NUM_KEYS).map(|key| 1 << key))
+                .collect(),
+            reward: Box::new(|_, _| 0.0),
+            done: Box::new(|_| false),
+        }
+    }
+}
+
+/// A reward of how much the byte at `address` went up, e.g. a score.
+pub fn increase(address: usize) -> Reward {
+    Box::new(move |before, after| after[address] as f64 - before[address] as f64)
+}
+
+/// Ends the episode once the byte at `address` holds `value`, e.g. no lives left.
+pub fn equals(address: usize, value: u8) -> Done {
+    Box::new(move |ram| ram[address] == value)
+}
+
+/// What `reset` and `step` return to the agent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Observation {
+    /// The whole RAM, `RAM_SIZE` bytes.
+    Ram,
+    /// The screen, one byte per pixel set to 0 or 1, row after row.
+    Screen,
+}
+
+/// The action passed to `Env::step` isn't one of the game's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidAction(pub usize);
+
+pub struct Env {
+    emu: Emulator,
+    recompiler: Recompiler,
+    game: Game,
+    observation: Observation,
+    frame_skip: usize,
+    screen: Vec<u8>,
+    before: Vec<u8>,
+    done: bool,
+}
+
+impl Env {
+    /// Each step runs `frame_skip` frames, stopping early if the episode ends.
+    pub fn new(game: Game, observation: Observation, frame_skip: usize) -> Self {
+        let mut env = Self {
+            emu: Emulator::new(),
+            recompiler: Recompiler::new(),
+            game,
+            observation,
+            frame_skip: frame_skip.max(1),
+            screen: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            before: vec![0; RAM_SIZE],
+            done: false,
+        };
+        env.reset(0);
+        env
+    }
+
+    /// Starts a new episode. Runs with the same seed and actions play out the same.
+    pub fn reset(&mut self, seed: u32) -> &[u8] {
+        self.emu.reset();
+        self.emu.set_quirks(self.game.quirks);
+        self.emu.seed(seed);
+        self.emu.load(&self.game.rom);
+        self.recompiler.clear();
+        self.done = false;
+        self.observe()
+    }
+
+    /// Holds the keys of `action` for the next frames. Returns the observation, the reward and
+    /// whether the episode is over, after which the environment has to be reset. Actions not
+    /// below `actions()` are rejected without running anything.
+    pub fn step(&mut self, action: usize) -> Result<(&[u8], f64, bool), InvalidAction> {
+        let keys = *self.game.actions.get(action).ok_or(InvalidAction(action))?;
+        for key in 0..NUM_KEYS {
+            self.emu.keypress(key, keys & (1 << key) != 0);
+        }
+
+        self.before.copy_from_slice(&self.emu.ram);
+        for _ in 0..self.frame_skip {
+            if self.done {
+                break;
+            }
+            self.recompiler
+                .run(&mut self.emu, self.game.ticks_per_frame);
+            self.emu.tick_timers();
+            self.done = (self.game.done)(&self.emu.ram);
+        }
+
+        let reward = (self.game.reward)(&self.before, &self.emu.ram);
+        let done = self.done;
+        Ok((self.observe(), reward, done))
+    }
+
+    /// Number of actions, the valid ones for `step` being below it.
+    pub fn actions(&self) -> usize {
+        self.game.actions.len()
+    }
+
+    pub fn emulator(&self) -> &Emulator {
+        &self.emu
+    }
+
+    fn observe(&mut self) -> &[u8] {
+        match self.observation {
+            Observation::Ram => &self.emu.ram,
+            Observation::Screen => {
+                for (byte, &pixel) in self.screen.iter_mut().zip(self.emu.screen.iter()) {
+                    *byte = pixel as u8;
+                }
+                &self.screen
+            }
+        }
+    }
+}
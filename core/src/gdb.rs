@@ -0,0 +1,458 @@
+//! GDB remote serial protocol server, see https://sourceware.org/gdb/onlinedocs/gdb/Remote-Protocol.html
+//!
+//! The stub only blocks to send its replies: the frontend calls `poll` once per frame to serve
+//! the debugger, and `tick` in place of `Emulator::tick` so breakpoints and single steps are
+//! honoured.
+
+use crate::*;
+
+use core::ops::Range;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+
+// Registers in the order of the `g` packet and the target description
+const REG_I: usize = NUM_REGS;
+const REG_PC: usize = NUM_REGS + 1;
+const REG_SP: usize = NUM_REGS + 2;
+const REG_DT: usize = NUM_REGS + 3;
+const REG_ST: usize = NUM_REGS + 4;
+const NUM_GDB_REGS: usize = NUM_REGS + 5;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip-r.chip8">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+pub struct GdbStub {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    incoming: Vec<u8>,
+    breakpoints: Vec<u16>,
+    halted: bool,
+}
+
+impl GdbStub {
+    /// Listens for a debugger on localhost. The emulator stays halted until one attaches and
+    /// resumes it, so the program can be debugged from its first instruction.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            stream: None,
+            incoming: Vec::new(),
+            breakpoints: Vec::new(),
+            halted: true,
+        })
+    }
+
+    /// The address the stub listens on, e.g. to find the port picked when binding port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Runs one instruction, unless the debugger halted the emulator. Breakpoints are checked
+    /// once the pc moves onto them, so resuming from one runs its instruction, and an instruction
+    /// stalled on it, like `WKP` waiting for a key, doesn't halt again.
+    pub fn tick(&mut self, emu: &mut Emulator) -> io::Result<()> {
+        if self.halted {
+            return Ok(());
+        }
+
+        let pc = emu.pc;
+        emu.tick();
+        if emu.pc != pc && self.breakpoints.contains(&emu.pc) {
+            self.halted = true;
+            self.send_stop(SIGTRAP)?;
+        }
+        Ok(())
+    }
+
+    /// Accepts a debugger connection and answers its pending packets.
+    pub fn poll(&mut self, emu: &mut Emulator) -> io::Result<()> {
+        if self.stream.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.stream = Some(stream);
+                    self.halted = true;
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(error) => return Err(error),
+            }
+        }
+
+        self.receive()?;
+        while let Some(packet) = self.next_packet()? {
+            let reply = self.handle(emu, &packet);
+            if let Some(reply) = reply {
+                self.send(&reply)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads everything available without blocking, dropping the connection when it closes
+    fn receive(&mut self) -> io::Result<()> {
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(());
+        };
+
+        let mut buffer = [0; 1024];
+        loop {
+            match stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.disconnect();
+                    return Ok(());
+                }
+                Ok(n) => self.incoming.extend_from_slice(&buffer[..n]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    // Extracts the next `$data#checksum` packet, acknowledging it. Ctrl-C interrupts arrive
+    // outside of packets as a single 0x03 byte.
+    fn next_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            match self.incoming.first() {
+                None => return Ok(None),
+                Some(0x03) => {
+                    self.incoming.remove(0);
+                    if !self.halted {
+                        self.halted = true;
+                        self.send_stop(SIGINT)?;
+                    }
+                }
+                Some(b'$') => break,
+                // Acks from the debugger, and noise
+                Some(_) => {
+                    self.incoming.remove(0);
+                }
+            }
+        }
+
+        let Some(end) = self.incoming.iter().position(|&byte| byte == b'#') else {
+            return Ok(None);
+        };
+        if self.incoming.len() < end + 3 {
+            return Ok(None);
+        }
+
+        let data = self.incoming[1..end].to_vec();
+        let checksum = std::str::from_utf8(&self.incoming[end + 1..end + 3])
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        self.incoming.drain(..end + 3);
+
+        if checksum != Some(checksum_of(&data)) {
+            self.write(b"-")?;
+            return Ok(None);
+        }
+        self.write(b"+")?;
+        Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+    }
+
+    // Answers a packet, None meaning the reply comes later (i.e. when continuing)
+    fn handle(&mut self, emu: &mut Emulator, packet: &str) -> Option<String> {
+        let Some(command) = packet.get(..1) else {
+            return Some(String::new());
+        };
+        let args = &packet[1..];
+        let reply = match command {
+            "?" => stop_reply(SIGTRAP),
+            "g" => (0..NUM_GDB_REGS)
+                .map(|reg| read_register(emu, reg))
+                .collect(),
+            "G" => write_registers(emu, args),
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(reg) if reg < NUM_GDB_REGS => read_register(emu, reg),
+                _ => error(),
+            },
+            "P" => write_register(emu, args),
+            "m" => read_memory(emu, args),
+            "M" => write_memory(emu, args),
+            "Z" | "z" => self.breakpoint(command == "Z", args),
+            "s" => {
+                jump_to(emu, args);
+                emu.tick();
+                stop_reply(SIGTRAP)
+            }
+            "c" => {
+                jump_to(emu, args);
+                self.halted = false;
+                return None;
+            }
+            "D" => {
+                self.breakpoints.clear();
+                self.halted = false;
+                self.send("OK").ok();
+                self.disconnect();
+                return None;
+            }
+            "k" => {
+                self.disconnect();
+                return None;
+            }
+            "H" => "OK".to_string(),
+            "q" => query(args),
+            _ => String::new(), // unsupported
+        };
+        Some(reply)
+    }
+
+    // Z0/Z1 set software/hardware breakpoints, which are the same thing here
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let mut fields = args.split(',');
+        let kind = fields.next();
+        let address = fields.next().and_then(|a| u16::from_str_radix(a, 16).ok());
+
+        match (kind, address) {
+            (Some("0" | "1"), Some(address)) => {
+                if insert {
+                    if !self.breakpoints.contains(&address) {
+                        self.breakpoints.push(address);
+                    }
+                } else {
+                    self.breakpoints.retain(|&b| b != address);
+                }
+                "OK".to_string()
+            }
+            _ => String::new(),
+        }
+    }
+
+    // The emulator runs on without a debugger
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.incoming.clear();
+        self.halted = false;
+    }
+
+    fn send_stop(&mut self, signal: u8) -> io::Result<()> {
+        self.send(&stop_reply(signal))
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        self.write(packet.as_bytes())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(());
+        };
+
+        // Packets are tiny, so blocking until a full send buffer drains takes no time
+        stream.set_nonblocking(false)?;
+        let result = stream.write_all(bytes);
+        stream.set_nonblocking(true)?;
+        result
+    }
+}
+
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        "PacketSize=1000;qXfer:features:read+".to_string()
+    } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        let Some((offset, length)) = parse_range(range) else {
+            return error();
+        };
+        let start = offset.min(TARGET_XML.len());
+        let end = offset.saturating_add(length).min(TARGET_XML.len());
+        // 'l' marks the last chunk, 'm' asks for more
+        let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+        format!("{}{}", marker, &TARGET_XML[start..end])
+    } else if args == "Attached" {
+        "1".to_string()
+    } else if args == "fThreadInfo" {
+        "m1".to_string()
+    } else if args == "sThreadInfo" {
+        "l".to_string()
+    } else if args == "C" {
+        "QC1".to_string()
+    } else {
+        String::new()
+    }
+}
+
+fn write_registers(emu: &mut Emulator, args: &str) -> String {
+    let mut hex = args;
+    for reg in 0..NUM_GDB_REGS {
+        let size = register_size(reg) * 2;
+        if hex.len() < size {
+            return error();
+        }
+        let (value, rest) = hex.split_at(size);
+        if !set_register(emu, reg, value) {
+            return error();
+        }
+        hex = rest;
+    }
+    "OK".to_string()
+}
+
+fn write_register(emu: &mut Emulator, args: &str) -> String {
+    let Some((reg, value)) = args.split_once('=') else {
+        return error();
+    };
+    match usize::from_str_radix(reg, 16) {
+        Ok(reg) if reg < NUM_GDB_REGS && set_register(emu, reg, value) => "OK".to_string(),
+        _ => error(),
+    }
+}
+
+fn register_size(reg: usize) -> usize {
+    match reg {
+        REG_I | REG_PC => 2,
+        _ => 1,
+    }
+}
+
+// Values go over the wire as little endian hex
+fn read_register(emu: &Emulator, reg: usize) -> String {
+    match reg {
+        REG_I => hex_u16(emu.i_reg),
+        REG_PC => hex_u16(emu.pc),
+        REG_SP => format!("{:02x}", emu.sp),
+        REG_DT => format!("{:02x}", emu.dt),
+        REG_ST => format!("{:02x}", emu.st),
+        _ => format!("{:02x}", emu.v_reg[reg]),
+    }
+}
+
+fn set_register(emu: &mut Emulator, reg: usize, hex: &str) -> bool {
+    let Some(bytes) = decode_hex(hex) else {
+        return false;
+    };
+    if bytes.len() != register_size(reg) {
+        return false;
+    }
+
+    let value = bytes[0];
+    match reg {
+        REG_I => emu.i_reg = u16::from_le_bytes([bytes[0], bytes[1]]),
+        REG_PC => {
+            let pc = u16::from_le_bytes([bytes[0], bytes[1]]);
+            if pc as usize >= RAM_SIZE {
+                return false;
+            }
+            emu.pc = pc;
+        }
+        REG_SP if value as usize <= STACK_SIZE => emu.sp = value as u16,
+        REG_SP => return false,
+        REG_DT => emu.dt = value,
+        REG_ST => emu.st = value,
+        _ => emu.v_reg[reg] = value,
+    }
+    true
+}
+
+fn read_memory(emu: &Emulator, args: &str) -> String {
+    match parse_range(args).and_then(ram_range) {
+        Some(range) => emu.ram[range]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+        None => error(),
+    }
+}
+
+fn write_memory(emu: &mut Emulator, args: &str) -> String {
+    let Some((range, data)) = args.split_once(':') else {
+        return error();
+    };
+    match (parse_range(range).and_then(ram_range), decode_hex(data)) {
+        (Some(range), Some(bytes)) if bytes.len() == range.len() => {
+            emu.store(range.start, &bytes);
+            "OK".to_string()
+        }
+        _ => error(),
+    }
+}
+
+// `s` and `c` may carry the address to resume from
+fn jump_to(emu: &mut Emulator, args: &str) {
+    if let Ok(address) = u16::from_str_radix(args, 16) {
+        if (address as usize) < RAM_SIZE {
+            emu.pc = address;
+        }
+    }
+}
+
+// Parses the `addr,length` pair used by several packets
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let (address, length) = args.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+// The RAM addressed by an `addr,length` pair, if it's all in RAM. Clients send any numbers, so
+// the end can overflow.
+fn ram_range((address, length): (usize, usize)) -> Option<Range<usize>> {
+    let end = address.checked_add(length)?;
+    (end <= RAM_SIZE).then_some(address..end)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn hex_u16(value: u16) -> String {
+    let [low, high] = value.to_le_bytes();
+    format!("{:02x}{:02x}", low, high)
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
@@ -0,0 +1,124 @@
+use crate::*;
+
+use core::ops::Range;
+
+/// Copy of the CPU registers at some point in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuState {
+    pub pc: u16,
+    pub sp: u16,
+    pub i: u16,
+    pub dt: u8,
+    pub st: u8,
+    pub v: [u8; NUM_REGS],
+    pub stack: [u16; STACK_SIZE],
+}
+
+// Read-only access for debuggers, tracers and other tools built on the core
+impl Emulator {
+    pub fn cpu_state(&self) -> CpuState {
+        CpuState {
+            pc: self.pc,
+            sp: self.sp,
+            i: self.i_reg,
+            dt: self.dt,
+            st: self.st,
+            v: self.v_reg,
+            stack: self.stack,
+        }
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i_reg
+    }
+
+    /// V0 to VF.
+    pub fn registers(&self) -> &[u8; NUM_REGS] {
+        &self.v_reg
+    }
+
+    /// Return addresses of the subroutines currently running, the innermost last.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    /// A slice of the RAM, panicking if `range` goes past `RAM_SIZE` like any slice would.
+    pub fn memory(&self, range: Range<usize>) -> &[u8] {
+        &self.ram[range]
+    }
+
+    /// Changes whenever the RAM does, so a copy of it only needs refreshing when this moved.
+    pub fn ram_version(&self) -> u32 {
+        self.ram_version
+    }
+
+    /// Delay and sound timers, in that order.
+    pub fn timers(&self) -> (u8, u8) {
+        (self.dt, self.st)
+    }
+
+    pub fn keys(&self) -> &[bool; NUM_KEYS] {
+        &self.keys
+    }
+}
+
+// Setters can put the machine in states no program could reach, so they're opt-in
+#[cfg(feature = "debug")]
+impl Emulator {
+    /// Restores every register, e.g. from an earlier `cpu_state()`. Like `load_state`, rejects a
+    /// pc outside of RAM or an sp past the top of the stack, and then changes nothing.
+    pub fn set_cpu_state(&mut self, state: &CpuState) -> Result<(), StateError> {
+        if state.pc as usize >= RAM_SIZE || state.sp as usize > STACK_SIZE {
+            return Err(StateError::Invalid);
+        }
+
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.i_reg = state.i;
+        self.dt = state.dt;
+        self.st = state.st;
+        self.v_reg = state.v;
+        self.stack = state.stack;
+        Ok(())
+    }
+
+    /// Rejects addresses outside of RAM, leaving the pc as it was.
+    pub fn set_pc(&mut self, value: u16) -> Result<(), StateError> {
+        if value as usize >= RAM_SIZE {
+            return Err(StateError::Invalid);
+        }
+        self.pc = value;
+        Ok(())
+    }
+
+    /// Rejects addresses outside of RAM, leaving I as it was.
+    pub fn set_i(&mut self, value: u16) -> Result<(), StateError> {
+        if value as usize >= RAM_SIZE {
+            return Err(StateError::Invalid);
+        }
+        self.i_reg = value;
+        Ok(())
+    }
+
+    pub fn set_register(&mut self, register: usize, value: u8) {
+        self.v_reg[register] = value;
+    }
+
+    pub fn set_timers(&mut self, dt: u8, st: u8) {
+        self.dt = dt;
+        self.st = st;
+    }
+
+    /// Copies `data` into the RAM, starting at `address`.
+    pub fn write_memory(&mut self, address: usize, data: &[u8]) {
+        self.store(address, data);
+    }
+}
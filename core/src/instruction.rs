@@ -0,0 +1,271 @@
+use core::fmt;
+
+/// A CHIP-8 instruction, with its operands split out of the opcode. Registers are indexes into
+/// V0–VF. Variants are named after the handlers in `Emulator::execute`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// `0000`: does nothing.
+    Nop,
+    /// `00E0`: clears the screen.
+    Clear,
+    /// `00EE`: returns from a subroutine.
+    Ret,
+    /// `1NNN`: jumps to NNN.
+    Jmp(u16),
+    /// `2NNN`: calls the subroutine at NNN.
+    Call(u16),
+    /// `3XNN`: skips the next instruction if VX == NN.
+    Seq(u8, u8),
+    /// `4XNN`: skips the next instruction if VX != NN.
+    Snq(u8, u8),
+    /// `5XY0`: skips the next instruction if VX == VY.
+    Seqr(u8, u8),
+    /// `6XNN`: VX = NN.
+    Ld(u8, u8),
+    /// `7XNN`: VX += NN, without carry.
+    Addiw(u8, u8),
+    /// `8XY0`: VX = VY.
+    Mv(u8, u8),
+    /// `8XY1`: VX |= VY.
+    Or(u8, u8),
+    /// `8XY2`: VX &= VY.
+    And(u8, u8),
+    /// `8XY3`: VX ^= VY.
+    Xor(u8, u8),
+    /// `8XY4`: VX += VY, VF = carry.
+    Add(u8, u8),
+    /// `8XY5`: VX -= VY, VF = no borrow.
+    Sub(u8, u8),
+    /// `8XY6`: VX >>= 1, VF = the bit shifted out. Y is ignored, but kept so the opcode survives
+    /// `encode`.
+    Shr(u8, u8),
+    /// `8XY7`: VX = VY - VX, VF = no borrow.
+    Sub2(u8, u8),
+    /// `8XYE`: VX <<= 1, VF = the bit shifted out. Y is ignored like in `Shr`.
+    Shl(u8, u8),
+    /// `9XY0`: skips the next instruction if VX != VY.
+    Snqr(u8, u8),
+    /// `ANNN`: I = NNN.
+    Ldi(u16),
+    /// `BNNN`: jumps to NNN + V0.
+    Jmp2(u16),
+    /// `CXNN`: VX = a random byte & NN.
+    Rnd(u8, u8),
+    /// `DXYN`: draws the N bytes high sprite at I, at (VX, VY). VF = whether a pixel was erased.
+    Draw(u8, u8, u8),
+    /// `EX9E`: skips the next instruction if key VX is down.
+    Skp(u8),
+    /// `EXA1`: skips the next instruction if key VX is up.
+    Snp(u8),
+    /// `FX07`: VX = delay timer.
+    Ldt(u8),
+    /// `FX0A`: waits for a key and stores it in VX.
+    Wkp(u8),
+    /// `FX15`: delay timer = VX.
+    Sdt(u8),
+    /// `FX18`: sound timer = VX.
+    Sst(u8),
+    /// `FX1E`: I += VX.
+    Iadd(u8),
+    /// `FX29`: I = address of the font sprite for digit VX.
+    Ldf(u8),
+    /// `FX33`: stores the decimal digits of VX at I, I + 1 and I + 2.
+    Sbcd(u8),
+    /// `FX55`: stores V0 to VX at I onwards.
+    Strr(u8),
+    /// `FX65`: loads V0 to VX from I onwards.
+    Ldr(u8),
+}
+
+/// The opcode isn't an instruction of CHIP-8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeError(pub u16);
+
+/// Splits an opcode into its instruction and operands.
+pub fn decode(op: u16) -> Result<Instruction, DecodeError> {
+    use Instruction::*;
+
+    let digit_1 = (op & 0xF000) >> 12;
+    let x = ((op & 0x0F00) >> 8) as u8;
+    let y = ((op & 0x00F0) >> 4) as u8;
+    let digit_4 = op & 0x000F;
+    let address = op & 0xFFF;
+    let value = (op & 0xFF) as u8;
+
+    Ok(match (digit_1, x, y, digit_4) {
+        (0, 0, 0, 0) => Nop,
+        (0, 0, 0xE, 0) => Clear,
+        (0, 0, 0xE, 0xE) => Ret,
+        (1, _, _, _) => Jmp(address),
+        (2, _, _, _) => Call(address),
+        (3, _, _, _) => Seq(x, value),
+        (4, _, _, _) => Snq(x, value),
+        (5, _, _, 0) => Seqr(x, y),
+        (6, _, _, _) => Ld(x, value),
+        (7, _, _, _) => Addiw(x, value),
+        (8, _, _, 0) => Mv(x, y),
+        (8, _, _, 1) => Or(x, y),
+        (8, _, _, 2) => And(x, y),
+        (8, _, _, 3) => Xor(x, y),
+        (8, _, _, 4) => Add(x, y),
+        (8, _, _, 5) => Sub(x, y),
+        (8, _, _, 6) => Shr(x, y),
+        (8, _, _, 7) => Sub2(x, y),
+        (8, _, _, 0xE) => Shl(x, y),
+        (9, _, _, 0) => Snqr(x, y),
+        (0xA, _, _, _) => Ldi(address),
+        (0xB, _, _, _) => Jmp2(address),
+        (0xC, _, _, _) => Rnd(x, value),
+        (0xD, _, _, _) => Draw(x, y, digit_4 as u8),
+        (0xE, _, 9, 0xE) => Skp(x),
+        (0xE, _, 0xA, 1) => Snp(x),
+        (0xF, _, 0, 7) => Ldt(x),
+        (0xF, _, 0, 0xA) => Wkp(x),
+        (0xF, _, 1, 5) => Sdt(x),
+        (0xF, _, 1, 8) => Sst(x),
+        (0xF, _, 1, 0xE) => Iadd(x),
+        (0xF, _, 2, 9) => Ldf(x),
+        (0xF, _, 3, 3) => Sbcd(x),
+        (0xF, _, 5, 5) => Strr(x),
+        (0xF, _, 6, 5) => Ldr(x),
+
+        (_, _, _, _) => return Err(DecodeError(op)),
+    })
+}
+
+/// Builds the opcode of an instruction, the inverse of `decode`. Operands too large for their
+/// field are truncated, e.g. registers to 4 bits and addresses to 12.
+pub fn encode(instruction: Instruction) -> u16 {
+    use Instruction::*;
+
+    let xnn = |prefix: u16, x: u8, value: u8| prefix | (x as u16 & 0xF) << 8 | value as u16;
+    let xyn = |prefix: u16, x: u8, y: u8, n: u8| {
+        prefix | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | (n as u16 & 0xF)
+    };
+    let xy = |suffix: u16, x: u8, y: u8| xyn(0x8000, x, y, suffix as u8);
+    let fx = |suffix: u16, x: u8| xnn(0xF000, x, suffix as u8);
+
+    match instruction {
+        Nop => 0x0000,
+        Clear => 0x00E0,
+        Ret => 0x00EE,
+        Jmp(address) => 0x1000 | (address & 0xFFF),
+        Call(address) => 0x2000 | (address & 0xFFF),
+        Seq(x, value) => xnn(0x3000, x, value),
+        Snq(x, value) => xnn(0x4000, x, value),
+        Seqr(x, y) => xyn(0x5000, x, y, 0),
+        Ld(x, value) => xnn(0x6000, x, value),
+        Addiw(x, value) => xnn(0x7000, x, value),
+        Mv(x, y) => xy(0, x, y),
+        Or(x, y) => xy(1, x, y),
+        And(x, y) => xy(2, x, y),
+        Xor(x, y) => xy(3, x, y),
+        Add(x, y) => xy(4, x, y),
+        Sub(x, y) => xy(5, x, y),
+        Shr(x, y) => xy(6, x, y),
+        Sub2(x, y) => xy(7, x, y),
+        Shl(x, y) => xy(0xE, x, y),
+        Snqr(x, y) => xyn(0x9000, x, y, 0),
+        Ldi(address) => 0xA000 | (address & 0xFFF),
+        Jmp2(address) => 0xB000 | (address & 0xFFF),
+        Rnd(x, value) => xnn(0xC000, x, value),
+        Draw(x, y, height) => xyn(0xD000, x, y, height),
+        Skp(x) => xnn(0xE000, x, 0x9E),
+        Snp(x) => xnn(0xE000, x, 0xA1),
+        Ldt(x) => fx(0x07, x),
+        Wkp(x) => fx(0x0A, x),
+        Sdt(x) => fx(0x15, x),
+        Sst(x) => fx(0x18, x),
+        Iadd(x) => fx(0x1E, x),
+        Ldf(x) => fx(0x29, x),
+        Sbcd(x) => fx(0x33, x),
+        Strr(x) => fx(0x55, x),
+        Ldr(x) => fx(0x65, x),
+    }
+}
+
+impl Instruction {
+    /// The mnemonic used by the emulator's source and the disassembler, e.g. `SEQ`.
+    pub fn mnemonic(&self) -> &'static str {
+        use Instruction::*;
+
+        match self {
+            Nop => "NOP",
+            Clear => "CLEAR",
+            Ret => "RET",
+            Jmp(_) => "JMP",
+            Call(_) => "CALL",
+            Seq(..) => "SEQ",
+            Snq(..) => "SNQ",
+            Seqr(..) => "SEQR",
+            Ld(..) => "LD",
+            Addiw(..) => "ADDIW",
+            Mv(..) => "MV",
+            Or(..) => "OR",
+            And(..) => "AND",
+            Xor(..) => "XOR",
+            Add(..) => "ADD",
+            Sub(..) => "SUB",
+            Shr(..) => "SHR",
+            Sub2(..) => "SUB2",
+            Shl(..) => "SHL",
+            Snqr(..) => "SNQR",
+            Ldi(_) => "LDI",
+            Jmp2(_) => "JMP2",
+            Rnd(..) => "RND",
+            Draw(..) => "DRAW",
+            Skp(_) => "SKP",
+            Snp(_) => "SNP",
+            Ldt(_) => "LDT",
+            Wkp(_) => "WKP",
+            Sdt(_) => "SDT",
+            Sst(_) => "SST",
+            Iadd(_) => "IADD",
+            Ldf(_) => "LDF",
+            Sbcd(_) => "SBCD",
+            Strr(_) => "STRR",
+            Ldr(_) => "LDR",
+        }
+    }
+}
+
+// Assembly in the emulator's mnemonics, e.g. `SEQ V3, 0x12`
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        let mnemonic = self.mnemonic();
+        match *self {
+            Nop | Clear | Ret => write!(f, "{}", mnemonic),
+            Jmp(address) | Call(address) | Ldi(address) | Jmp2(address) => {
+                write!(f, "{} 0x{:03X}", mnemonic, address)
+            }
+            Seq(x, value) | Snq(x, value) | Ld(x, value) | Addiw(x, value) | Rnd(x, value) => {
+                write!(f, "{} V{:X}, 0x{:02X}", mnemonic, x, value)
+            }
+            Draw(x, y, height) => write!(f, "DRAW V{:X}, V{:X}, {}", x, y, height),
+            Seqr(x, y)
+            | Snqr(x, y)
+            | Mv(x, y)
+            | Or(x, y)
+            | And(x, y)
+            | Xor(x, y)
+            | Add(x, y)
+            | Sub(x, y)
+            | Sub2(x, y) => write!(f, "{} V{:X}, V{:X}", mnemonic, x, y),
+            Shr(x, _)
+            | Shl(x, _)
+            | Skp(x)
+            | Snp(x)
+            | Ldt(x)
+            | Wkp(x)
+            | Sdt(x)
+            | Sst(x)
+            | Iadd(x)
+            | Ldf(x)
+            | Sbcd(x)
+            | Strr(x)
+            | Ldr(x) => write!(f, "{} V{:X}", mnemonic, x),
+        }
+    }
+}
@@ -1,14 +1,52 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "debug")]
+pub mod cheat;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "decode-cache")]
+pub mod env;
+#[cfg(feature = "gdb")]
+pub mod gdb;
+mod inspect;
+mod instruction;
+#[cfg(feature = "std")]
+pub mod profile;
+mod quirks;
+#[cfg(feature = "decode-cache")]
+pub mod recompiler;
+mod rng;
+#[cfg(feature = "std")]
+pub mod selfmod;
+mod state;
+
+pub use inspect::CpuState;
+pub use instruction::{decode, encode, DecodeError, Instruction};
+pub use quirks::Quirks;
+pub use state::{StateError, STATE_SIZE};
+
+use core::ops::Range;
+#[cfg(feature = "decode-cache")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "rand")]
 use rand::random;
+use rng::Rng;
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 
-const RAM_SIZE: usize = 4096;
-const NUM_REGS: usize = 16;
-const NUM_KEYS: usize = 16;
-const STACK_SIZE: usize = 16;
+pub const RAM_SIZE: usize = 4096;
+pub const NUM_REGS: usize = 16;
+pub const NUM_KEYS: usize = 16;
+pub const STACK_SIZE: usize = 16;
 const START_ADDR: u16 = 0x200;
 
+#[cfg(feature = "decode-cache")]
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
 const FONTSET_SIZE: usize = 80;
 const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -29,35 +67,167 @@ const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+/// Area of the screen touched since it was last read, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DirtyRect {
+    fn pixel(x: usize, y: usize) -> Self {
+        Self {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    fn full_screen() -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+        }
+    }
+
+    // Grows the rectangle until it covers the (x, y) pixel
+    fn include(&mut self, x: usize, y: usize) {
+        let right = (self.x + self.width).max(x + 1);
+        let bottom = (self.y + self.height).max(y + 1);
+        self.x = self.x.min(x);
+        self.y = self.y.min(y);
+        self.width = right - self.x;
+        self.height = bottom - self.y;
+    }
+}
+
+// Progress of FX0A when it waits for a key release
+#[derive(Clone, Copy, PartialEq)]
+enum KeyWait {
+    Idle,
+    Waiting,
+    Pressed(usize),
+}
+
 pub struct Emulator {
     pc: u16, // program counter
     sp: u16, // stack counter
     dt: u8,  // delay timer
     st: u8,  // sound timer
     ram: [u8; RAM_SIZE],
+    ram_version: u32, // bumped by every change to the RAM, so copies of it know they're stale
+    #[cfg(feature = "decode-cache")]
+    decoded: [Option<Instruction>; RAM_SIZE], // instructions already decoded, cleared when their bytes change
+    #[cfg(feature = "decode-cache")]
+    writes: u32, // bumped by writes over decoded instructions, so the recompiler rechecks its blocks
+    #[cfg(feature = "decode-cache")]
+    id: u64, // unique to each emulator, so a recompiler notices it's given another one
     screen: [bool; SCREEN_HEIGHT * SCREEN_WIDTH],
+    dirty: Option<DirtyRect>, // screen area changed since the last display_changed() call
     keys: [bool; NUM_KEYS],
+    key_presses: [bool; NUM_KEYS], // key went down since FX0A started waiting
+    key_releases: [bool; NUM_KEYS], // key went up after its last press
+    key_wait: KeyWait,
     v_reg: [u8; NUM_REGS],
     i_reg: u16,
     stack: [u16; STACK_SIZE],
+    quirks: Quirks,
+    vblank: bool, // a frame ended since the last draw, only used by the display wait quirk
+    fault: Option<DecodeError>, // the last tick stopped at an opcode that isn't an instruction
+    rng: Rng,
+    #[cfg(feature = "rand")]
+    seeded: bool, // RND uses rng instead of rand's entropy once seeded
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Emulator {
+    /// Reports whether the screen changed since the last call, so frontends can skip redrawing
+    /// identical frames. Reading it also clears the dirty rectangle.
+    pub fn display_changed(&mut self) -> bool {
+        self.take_dirty_rect().is_some()
+    }
+
+    /// Returns the area of the screen changed by `clear` and `draw` since the last call, if any.
+    /// Sprites wrapping around the edges may make it span the whole width or height.
+    pub fn take_dirty_rect(&mut self) -> Option<DirtyRect> {
+        self.dirty.take()
+    }
+
     pub fn get_display(&self) -> &[bool] {
         &self.screen
     }
 
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Seeds the built-in generator used by RND. Without the `rand` feature there's no entropy
+    /// source, so every run gets the same random numbers unless seeded, e.g. from a timer. With
+    /// it, seeding switches RND from rand to the built-in generator, to replay the same run.
+    pub fn seed(&mut self, seed: u32) {
+        self.rng = Rng::new(seed);
+        #[cfg(feature = "rand")]
+        {
+            self.seeded = true;
+        }
+    }
+
     pub fn keypress(&mut self, i: usize, pressed: bool) {
+        if pressed && !self.keys[i] {
+            self.key_presses[i] = true;
+            self.key_releases[i] = false;
+        } else if !pressed && self.keys[i] {
+            self.key_releases[i] = true;
+        }
         self.keys[i] = pressed;
     }
 
     pub fn load(&mut self, data: &[u8]) {
-        let start = START_ADDR as usize;
-        let end = (START_ADDR as usize) + data.len();
+        self.store(START_ADDR as usize, data);
+    }
+
+    // Writes bytes to RAM, forgetting the instructions decoded from those that change. Storing
+    // the bytes already there leaves decoded code, and the recompiler's blocks, alone.
+    fn store(&mut self, address: usize, data: &[u8]) {
+        let ram = &mut self.ram[address..address + data.len()];
+        let first = ram.iter().zip(data).position(|(old, new)| old != new);
+        let last = ram.iter().zip(data).rposition(|(old, new)| old != new);
+        if let (Some(first), Some(last)) = (first, last) {
+            ram.copy_from_slice(data);
+            self.ram_version = self.ram_version.wrapping_add(1);
+            self.invalidate(address + first..address + last + 1);
+        }
+    }
 
-        self.ram[start..end].copy_from_slice(data);
+    // Forgets the instructions decoded from bytes in the range, which is about to be or was just
+    // written. The instruction starting one byte earlier overlaps it too.
+    #[cfg(feature = "decode-cache")]
+    fn invalidate(&mut self, range: Range<usize>) {
+        let start = range.start.saturating_sub(1);
+        let end = range.end.min(RAM_SIZE);
+        if start < end && self.decoded[start..end].iter().any(Option::is_some) {
+            self.decoded[start..end].fill(None);
+            self.writes = self.writes.wrapping_add(1);
+        }
     }
 
+    #[cfg(not(feature = "decode-cache"))]
+    fn invalidate(&mut self, _range: Range<usize>) {}
+
     pub fn new() -> Self {
         let mut emu = Self {
             pc: START_ADDR,
@@ -65,11 +235,28 @@ impl Emulator {
             dt: 0,
             st: 0,
             ram: [0; RAM_SIZE],
+            ram_version: 0,
+            #[cfg(feature = "decode-cache")]
+            decoded: [None; RAM_SIZE],
+            #[cfg(feature = "decode-cache")]
+            writes: 0,
+            #[cfg(feature = "decode-cache")]
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             screen: [false; SCREEN_HEIGHT * SCREEN_WIDTH],
+            dirty: Some(DirtyRect::full_screen()),
             keys: [false; NUM_KEYS],
+            key_presses: [false; NUM_KEYS],
+            key_releases: [false; NUM_KEYS],
+            key_wait: KeyWait::Idle,
             v_reg: [0; NUM_REGS],
             i_reg: 0,
             stack: [0; STACK_SIZE],
+            quirks: Quirks::default(),
+            vblank: false,
+            fault: None,
+            rng: Rng::new(0),
+            #[cfg(feature = "rand")]
+            seeded: false,
         };
 
         // copies bitmap fonts set to RAM using slices
@@ -83,21 +270,44 @@ impl Emulator {
         self.dt = 0;
         self.st = 0;
         self.ram = [0; RAM_SIZE];
+        self.ram_version = self.ram_version.wrapping_add(1);
+        self.invalidate(0..RAM_SIZE);
         self.screen = [false; SCREEN_HEIGHT * SCREEN_WIDTH];
+        self.dirty = Some(DirtyRect::full_screen());
         self.keys = [false; NUM_KEYS];
+        self.key_presses = [false; NUM_KEYS];
+        self.key_releases = [false; NUM_KEYS];
+        self.key_wait = KeyWait::Idle;
         self.v_reg = [0; NUM_REGS];
         self.i_reg = 0;
         self.stack = [0; STACK_SIZE];
+        self.vblank = false;
+        self.fault = None;
 
         self.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
     }
 
+    /// Runs the instruction at the pc. An opcode that isn't an instruction stops the emulator
+    /// there instead, until its bytes or the pc change, and is reported by `fault`.
     pub fn tick(&mut self) {
-        let op = self.fetch();
-        self.execute(op);
+        match self.fetch() {
+            Ok(instruction) => {
+                self.fault = None;
+                self.execute(instruction);
+            }
+            Err(error) => self.fault = Some(error),
+        }
+    }
+
+    /// The opcode the last tick stopped at, if it isn't an instruction.
+    pub fn fault(&self) -> Option<DecodeError> {
+        self.fault
     }
 
+    /// Ends the current frame, counting the timers down. Call it 60 times per second.
     pub fn tick_timers(&mut self) {
+        self.vblank = true;
+
         if self.dt > 0 {
             self.dt -= 1;
         }
@@ -111,7 +321,7 @@ impl Emulator {
     }
 
     /// ADD: register_1 += register_2, with carry
-    fn add(&mut self, register_1: u16, register_2: u16) {
+    fn add(&mut self, register_1: u8, register_2: u8) {
         let r1 = register_1 as usize;
         let r2 = register_2 as usize;
 
@@ -123,22 +333,20 @@ impl Emulator {
     }
 
     /// ADDIW: register_1 += value, wrapped add
-    fn addiw(&mut self, op: u16, register: u16) {
+    fn addiw(&mut self, register: u8, value: u8) {
         let r1 = register as usize;
-        let value = (op & 0xFF) as u8;
         self.v_reg[r1] = self.v_reg[r1].wrapping_add(value);
     }
 
     /// AND: register &= register_2
-    fn and(&mut self, register_1: u16, register_2: u16) {
+    fn and(&mut self, register_1: u8, register_2: u8) {
         let r1 = register_1 as usize;
         let r2 = register_2 as usize;
         self.v_reg[r1] &= self.v_reg[r2];
     }
 
     /// CALL: call subroutine
-    fn call(&mut self, op: u16) {
-        let address = op & 0xFFF;
+    fn call(&mut self, address: u16) {
         self.push(self.pc);
         self.pc = address;
     }
@@ -146,19 +354,29 @@ impl Emulator {
     /// CLEAR => clear screen
     fn clear(&mut self) {
         self.screen = [false; SCREEN_HEIGHT * SCREEN_WIDTH];
+        self.dirty = Some(DirtyRect::full_screen());
     }
 
     // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#dxyn-display
-    fn draw(&mut self, register_1: u16, register_2: u16, height: u16) {
+    fn draw(&mut self, register_1: u8, register_2: u8, height: u8) {
+        if self.quirks.display_wait {
+            if !self.vblank {
+                // Redo opcode until the frame ends
+                self.pc -= 2;
+                return;
+            }
+            self.vblank = false;
+        }
+
         // coordinates indicate where the sprite will be drawed
         let x = self.v_reg[register_1 as usize] as u16;
         let y = self.v_reg[register_2 as usize] as u16;
 
         // Keep track if any pixels were flipped
         let mut flipped = false;
-        for row in 0..height {
+        for row in 0..height as u16 {
             // Load pixels from sprite, address is stores on i_reg
-            let address = self.i_reg + row as u16;
+            let address = self.i_reg + row;
             let pixels = self.ram[address as usize];
 
             // In CHIP-8, all sprites are 8 pixels wide
@@ -173,6 +391,10 @@ impl Emulator {
                     let idx = SCREEN_WIDTH * current_y + current_x;
                     flipped |= self.screen[idx];
                     self.screen[idx] ^= true;
+                    match &mut self.dirty {
+                        Some(dirty) => dirty.include(current_x, current_y),
+                        None => self.dirty = Some(DirtyRect::pixel(current_x, current_y)),
+                    }
                 }
             }
         }
@@ -185,121 +407,129 @@ impl Emulator {
         }
     }
 
-    fn execute(&mut self, op: u16) {
-        let digit_1 = (op & 0xF000) >> 12;
-        let digit_2 = (op & 0x0F00) >> 8;
-        let digit_3 = (op & 0x00F0) >> 4;
-        let digit_4 = op & 0x000F;
-
-        match (digit_1, digit_2, digit_3, digit_4) {
-            (0, 0, 0, 0) => return,                                 // NOP
-            (0, 0, 0xE, 0) => self.clear(),                         // CLEAR SCREEN
-            (0, 0, 0xE, 0xE) => self.ret(),                         // RET
-            (1, _, _, _) => self.jmp(op),                           // JMP
-            (2, _, _, _) => self.call(op),                          // CALL
-            (3, _, _, _) => self.seq(op, digit_2),                  // SEQ
-            (4, _, _, _) => self.snq(op, digit_2),                  // SNQ
-            (5, _, _, 0) => self.seqr(digit_2, digit_3),            // SEQR
-            (6, _, _, _) => self.ld(op, digit_2),                   // LD
-            (7, _, _, _) => self.addiw(op, digit_2),                // ADDIW
-            (8, _, _, 0) => self.mv(digit_2, digit_3),              // MV
-            (8, _, _, 1) => self.or(digit_2, digit_3),              // OR
-            (8, _, _, 2) => self.and(digit_2, digit_3),             // AND
-            (8, _, _, 3) => self.xor(digit_2, digit_3),             // XOR
-            (8, _, _, 4) => self.add(digit_2, digit_3),             // ADD
-            (8, _, _, 5) => self.sub(digit_2, digit_3),             // SUB
-            (8, _, _, 6) => self.shr(digit_2),                      // SHR
-            (8, _, _, 7) => self.sub2(digit_2, digit_3),            // SUB2
-            (8, _, _, 0xE) => self.shl(digit_2),                    // SHL
-            (9, _, _, 0) => self.snqr(digit_2, digit_3),            // SNQR
-            (0xA, _, _, _) => self.ldi(op),                         // LDI
-            (0xB, _, _, _) => self.jmp2(op),                        // JMP2
-            (0xC, _, _, _) => self.rnd(op, digit_2),                // RND
-            (0xD, _, _, _) => self.draw(digit_2, digit_3, digit_4), // DRAW
-            (0xE, _, 9, 0xE) => self.skp(digit_2),                  //SKP
-            (0xE, _, 0xA, 1) => self.snp(digit_2),                  // SNP
-            (0xF, _, 0, 7) => self.ldt(digit_2),                    // LDT
-            (0xF, _, 0, 0xA) => self.wkp(digit_2),                  // WKP
-            (0xF, _, 1, 5) => self.sdt(digit_2),                    // SDT
-            (0xF, _, 1, 8) => self.sst(digit_2),                    // SST
-            (0xF, _, 1, 0xE) => self.iadd(digit_2),                 // IADD
-            (0xF, _, 2, 9) => self.ldf(digit_2),                    // LDF
-            (0xF, _, 3, 3) => self.sbcd(digit_2),                   // SBCD
-            (0xF, _, 5, 5) => self.strr(digit_2),                   // STRR,
-            (0xF, _, 6, 5) => self.ldr(digit_2),                    // LDR
-
-            (_, _, _, _) => unimplemented!("Unimplemented op code: { }", op),
+    fn execute(&mut self, instruction: Instruction) {
+        use Instruction::*;
+
+        match instruction {
+            Nop => (),
+            Clear => self.clear(),
+            Ret => self.ret(),
+            Jmp(address) => self.jmp(address),
+            Call(address) => self.call(address),
+            Seq(x, value) => self.seq(x, value),
+            Snq(x, value) => self.snq(x, value),
+            Seqr(x, y) => self.seqr(x, y),
+            Ld(x, value) => self.ld(x, value),
+            Addiw(x, value) => self.addiw(x, value),
+            Mv(x, y) => self.mv(x, y),
+            Or(x, y) => self.or(x, y),
+            And(x, y) => self.and(x, y),
+            Xor(x, y) => self.xor(x, y),
+            Add(x, y) => self.add(x, y),
+            Sub(x, y) => self.sub(x, y),
+            Shr(x, _) => self.shr(x),
+            Sub2(x, y) => self.sub2(x, y),
+            Shl(x, _) => self.shl(x),
+            Snqr(x, y) => self.snqr(x, y),
+            Ldi(address) => self.ldi(address),
+            Jmp2(address) => self.jmp2(address),
+            Rnd(x, value) => self.rnd(x, value),
+            Draw(x, y, height) => self.draw(x, y, height),
+            Skp(x) => self.skp(x),
+            Snp(x) => self.snp(x),
+            Ldt(x) => self.ldt(x),
+            Wkp(x) => self.wkp(x),
+            Sdt(x) => self.sdt(x),
+            Sst(x) => self.sst(x),
+            Iadd(x) => self.iadd(x),
+            Ldf(x) => self.ldf(x),
+            Sbcd(x) => self.sbcd(x),
+            Strr(x) => self.strr(x),
+            Ldr(x) => self.ldr(x),
         }
     }
 
-    // Big Endian words; opcodes has two bytes length
-    fn fetch(&mut self) -> u16 {
-        let higher_byte = self.ram[self.pc as usize] as u16;
-        let lower_byte = self.ram[(self.pc + 1) as usize] as u16;
-        let op = (higher_byte << 8) | lower_byte; // bitwise concatenation to gen the opcode
+    // Big Endian words; opcodes has two bytes length. With the decode cache, each address is only
+    // decoded the first time it runs, and again after a write to its bytes.
+    fn fetch(&mut self) -> Result<Instruction, DecodeError> {
+        let pc = self.pc as usize;
+        #[cfg(feature = "decode-cache")]
+        let instruction = match self.decoded[pc] {
+            Some(instruction) => instruction,
+            None => {
+                let instruction = self.decode_at(pc)?;
+                self.decoded[pc] = Some(instruction);
+                instruction
+            }
+        };
+        #[cfg(not(feature = "decode-cache"))]
+        let instruction = self.decode_at(pc)?;
         self.pc += 2;
-        op
+        Ok(instruction)
+    }
+
+    fn decode_at(&self, pc: usize) -> Result<Instruction, DecodeError> {
+        let higher_byte = self.ram[pc] as u16;
+        let lower_byte = self.ram[pc + 1] as u16;
+        let op = (higher_byte << 8) | lower_byte; // bitwise concatenation to gen the opcode
+        decode(op)
     }
 
     /// IADD: increment register I with a offset stored in another register
-    fn iadd(&mut self, register: u16) {
+    fn iadd(&mut self, register: u8) {
         let r1 = register as usize;
         self.i_reg = self.i_reg.wrapping_add(self.v_reg[r1] as u16);
     }
 
     /// JMP: jump to address encoded in op code
-    fn jmp(&mut self, op: u16) {
-        let address = op & 0xFFF;
+    fn jmp(&mut self, address: u16) {
         self.pc = address;
     }
 
     /// JMP2: jump to address encoded in op code + V0
-    fn jmp2(&mut self, op: u16) {
-        let address = op & 0xFFF;
+    fn jmp2(&mut self, address: u16) {
         self.pc = (self.v_reg[0] as u16) + address;
     }
 
     /// LD: The interpreter puts the value into register_1
-    fn ld(&mut self, op: u16, register: u16) {
+    fn ld(&mut self, register: u8, value: u8) {
         let r1 = register as usize;
-        let value = (op & 0xFF) as u8;
         self.v_reg[r1] = value;
     }
 
     /// LDI: The value of register I is set to a value encoded in the opcode.
-    fn ldi(&mut self, op: u16) {
-        let address = op & 0xFFF;
+    fn ldi(&mut self, address: u16) {
         self.i_reg = address;
     }
 
     /// LDF: load font address into register I
-    fn ldf(&mut self, register: u16) {
+    fn ldf(&mut self, register: u8) {
         let r1 = register as usize;
         self.i_reg = 5 * (self.v_reg[r1] as u16);
     }
 
     /// LDR: load a slice from the memory on the registers
-    fn ldr(&mut self, range: u16) {
+    fn ldr(&mut self, range: u8) {
         for i in 0..=range {
             self.v_reg[i as usize] = self.ram[(self.i_reg as usize) + (i as usize)];
         }
     }
 
     /// LDT: load delta timer value in a register
-    fn ldt(&mut self, register: u16) {
+    fn ldt(&mut self, register: u8) {
         let r1 = register as usize;
         self.v_reg[r1] = self.dt;
     }
 
     /// MV: Stores the value of register_2 in register_1
-    fn mv(&mut self, register_1: u16, register_2: u16) {
+    fn mv(&mut self, register_1: u8, register_2: u8) {
         let r1 = register_1 as usize;
         let r2 = register_2 as usize;
         self.v_reg[r1] = self.v_reg[r2];
     }
 
     /// OR: register_1 |= register_2
-    fn or(&mut self, register_1: u16, register_2: u16) {
+    fn or(&mut self, register_1: u8, register_2: u8) {
         let r1 = register_1 as usize;
         let r2 = register_2 as usize;
         self.v_reg[r1] |= self.v_reg[r2];
@@ -322,45 +552,48 @@ impl Emulator {
     }
 
     /// RND: generate a random number with a bitwise AND base on a register value and store the result in the same register
-    fn rnd(&mut self, op: u16, register: u16) {
+    fn rnd(&mut self, register: u8, value: u8) {
         let r1 = register as usize;
-        let value = (op & 0xFF) as u8;
 
-        let rng: u8 = random();
+        #[cfg(feature = "rand")]
+        let rng: u8 = if self.seeded {
+            self.rng.next_u8()
+        } else {
+            random()
+        };
+        #[cfg(not(feature = "rand"))]
+        let rng = self.rng.next_u8();
         self.v_reg[r1] = rng & value;
     }
 
     /// SBCD: store the BCD value of a register in memory.
-    fn sbcd(&mut self, register: u16) {
+    fn sbcd(&mut self, register: u8) {
         let r1 = register as usize;
-        let value = self.v_reg[r1] as f32;
+        let value = self.v_reg[r1];
 
-        let hundreds = (value / 100.0).floor() as u8;
-        let tens = ((value / 10.0) % 10.0).floor() as u8;
-        let ones = (value % 10.0).floor() as u8;
+        let hundreds = value / 100;
+        let tens = (value / 10) % 10;
+        let ones = value % 10;
 
-        self.ram[self.i_reg as usize] = hundreds;
-        self.ram[(self.i_reg + 1) as usize] = tens;
-        self.ram[(self.i_reg + 2) as usize] = ones;
+        self.store(self.i_reg as usize, &[hundreds, tens, ones]);
     }
 
     /// SDT: set/store delta timer.
-    fn sdt(&mut self, register: u16) {
+    fn sdt(&mut self, register: u8) {
         let r1 = register as usize;
         self.dt = self.v_reg[r1];
     }
 
     /// SEQ: skip if register equal to value.
-    fn seq(&mut self, op: u16, register: u16) {
+    fn seq(&mut self, register: u8, value: u8) {
         let r1 = register as usize;
-        let value = (op & 0xFF) as u8;
         if self.v_reg[r1] == value {
             self.pc += 2;
         }
     }
 
     /// SEQE: skip if register_1 equal to register_2.
-    fn seqr(&mut self, register_1: u16, register_2: u16) {
+    fn seqr(&mut self, register_1: u8, register_2: u8) {
         let r1 = register_1 as usize;
         let r2 = register_2 as usize;
         if self.v_reg[r1] == self.v_reg[r2] {
@@ -369,7 +602,7 @@ impl Emulator {
     }
 
     /// SHL: shift-left value in register, add flag in the VF.
-    fn shl(&mut self, register: u16) {
+    fn shl(&mut self, register: u8) {
         let r1 = register as usize;
 
         let msb = (self.v_reg[r1] >> 7) & 1;
@@ -378,7 +611,7 @@ impl Emulator {
     }
 
     /// SHL: shift-right value in register, add flag in the VF.
-    fn shr(&mut self, register: u16) {
+    fn shr(&mut self, register: u8) {
         let r1 = register as usize;
 
         //  Unfortunately, there isn’t a built-in Rust u8 operator to catch the dropped bit, so we will have to do it ourself
@@ -388,7 +621,7 @@ impl Emulator {
     }
 
     /// SKP: skip if key is pressed.
-    fn skp(&mut self, register: u16) {
+    fn skp(&mut self, register: u8) {
         let r1 = register as usize;
         let key = self.keys[self.v_reg[r1] as usize];
         if key {
@@ -397,7 +630,7 @@ impl Emulator {
     }
 
     /// SNP: skip if key is not pressed.
-    fn snp(&mut self, register: u16) {
+    fn snp(&mut self, register: u8) {
         let r1 = register as usize;
         let key = self.keys[self.v_reg[r1] as usize];
         if !key {
@@ -406,16 +639,15 @@ impl Emulator {
     }
 
     /// SNQ: skip if register not equal to value.
-    fn snq(&mut self, op: u16, register: u16) {
+    fn snq(&mut self, register: u8, value: u8) {
         let r1 = register as usize;
-        let value = (op & 0xFF) as u8;
         if self.v_reg[r1] != value {
             self.pc += 2;
         }
     }
 
     /// SNQR: skip if register_1 not equal to register_2.
-    fn snqr(&mut self, register_1: u16, register_2: u16) {
+    fn snqr(&mut self, register_1: u8, register_2: u8) {
         let r1 = register_1 as usize;
         let r2 = register_2 as usize;
 
@@ -425,20 +657,19 @@ impl Emulator {
     }
 
     /// STRR: store a range of registers values in memory.
-    fn strr(&mut self, range: u16) {
-        for i in 0..=range {
-            self.ram[(self.i_reg as usize) + (i as usize)] = self.v_reg[i as usize];
-        }
+    fn strr(&mut self, range: u8) {
+        let values = self.v_reg;
+        self.store(self.i_reg as usize, &values[..=range as usize]);
     }
 
     /// SDT: set/store sound timer.
-    fn sst(&mut self, register: u16) {
+    fn sst(&mut self, register: u8) {
         let r1 = register as usize;
         self.st = self.v_reg[r1];
     }
 
     /// SUB: register_1 -= register_2, with carry.
-    fn sub(&mut self, register_1: u16, register_2: u16) {
+    fn sub(&mut self, register_1: u8, register_2: u8) {
         let r1 = register_1 as usize;
         let r2 = register_2 as usize;
 
@@ -450,7 +681,7 @@ impl Emulator {
     }
 
     /// SUB: register_2 -= register_1, with carry.
-    fn sub2(&mut self, register_1: u16, register_2: u16) {
+    fn sub2(&mut self, register_1: u8, register_2: u8) {
         let r1 = register_1 as usize;
         let r2 = register_2 as usize;
 
@@ -462,8 +693,13 @@ impl Emulator {
     }
 
     // WKP: wait for key to be pressed.
-    fn wkp(&mut self, register: u16) {
+    fn wkp(&mut self, register: u8) {
         let r1 = register as usize;
+        if self.quirks.key_release {
+            self.wkp_release(r1);
+            return;
+        }
+
         let mut pressed = false;
         for i in 0..self.keys.len() {
             if self.keys[i] {
@@ -479,8 +715,37 @@ impl Emulator {
         }
     }
 
+    // WKP with the key release quirk: wait for a key to be pressed and then released.
+    fn wkp_release(&mut self, r1: usize) {
+        if self.key_wait == KeyWait::Idle {
+            // Edges from before the wait started are stale, and keys held since don't count
+            self.key_presses = [false; NUM_KEYS];
+            self.key_releases = [false; NUM_KEYS];
+            self.key_wait = KeyWait::Waiting;
+        }
+
+        if self.key_wait == KeyWait::Waiting {
+            // Only keys going down count, including those tapped and released between two ticks
+            let pressed = (0..NUM_KEYS).find(|&i| self.key_presses[i]);
+            if let Some(key) = pressed {
+                self.key_wait = KeyWait::Pressed(key);
+            }
+        }
+
+        match self.key_wait {
+            KeyWait::Pressed(key) if self.key_releases[key] => {
+                self.v_reg[r1] = key as u8;
+                self.key_wait = KeyWait::Idle;
+            }
+            _ => {
+                // Redo opcode
+                self.pc -= 2;
+            }
+        }
+    }
+
     /// XOR: register_1 ^= register_2.
-    fn xor(&mut self, register_1: u16, register_2: u16) {
+    fn xor(&mut self, register_1: u8, register_2: u8) {
         let r1 = register_1 as usize;
         let r2 = register_2 as usize;
         self.v_reg[r1] ^= self.v_reg[r2];
@@ -0,0 +1,175 @@
+//! Instruction-level profiler.
+//!
+//! `record` is called before every `Emulator::tick` and counts the instruction about to run: per
+//! address, per opcode class and per call stack. The call stack follows the emulator's own, so
+//! time is charged to the subroutines entered through `CALL` and left through `RET`. A `DRAW`
+//! stalled by the display wait quirk counts once per attempt, as it uses up a tick each time.
+
+use crate::analysis::disassemble;
+use crate::*;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+// Rows of each table in the report
+const REPORT_ROWS: usize = 20;
+
+pub struct Profiler {
+    counts: Vec<u64>,
+    opcodes: Vec<u16>,
+    classes: BTreeMap<&'static str, u64>,
+    // Entry points of the subroutines being run, innermost last
+    stack: Vec<u16>,
+    stacks: HashMap<Vec<u16>, u64>,
+    total: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            counts: vec![0; RAM_SIZE],
+            opcodes: vec![0; RAM_SIZE],
+            classes: BTreeMap::new(),
+            stack: Vec::new(),
+            stacks: HashMap::new(),
+            total: 0,
+        }
+    }
+
+    /// Counts the instruction the emulator is about to run.
+    pub fn record(&mut self, emu: &Emulator) {
+        let pc = emu.pc;
+        // A CALL pushes its return address, so the first instruction after a push is the
+        // subroutine's entry point; a RET pops it
+        let depth = emu.sp as usize;
+        self.stack.truncate(depth);
+        while self.stack.len() < depth {
+            self.stack.push(pc);
+        }
+
+        let address = pc as usize % RAM_SIZE;
+        let op = ((emu.ram[address] as u16) << 8) | emu.ram[(address + 1) % RAM_SIZE] as u16;
+        self.counts[address] += 1;
+        self.opcodes[address] = op;
+        *self
+            .classes
+            .entry(decode(op).map_or("???", |instruction| instruction.mnemonic()))
+            .or_default() += 1;
+        match self.stacks.get_mut(&self.stack) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.stack.clone(), 1);
+            }
+        }
+        self.total += 1;
+    }
+
+    /// Instructions recorded so far.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Number of times the instruction at `address` ran.
+    pub fn count(&self, address: usize) -> u64 {
+        self.counts[address]
+    }
+
+    /// Instructions run by each subroutine, itself and through the subroutines it calls. The
+    /// code outside of any subroutine is at `START_ADDR`.
+    pub fn subroutines(&self) -> BTreeMap<u16, (u64, u64)> {
+        let mut cycles: BTreeMap<u16, (u64, u64)> = BTreeMap::new();
+        for (stack, &count) in &self.stacks {
+            let innermost = stack.last().copied().unwrap_or(START_ADDR);
+            cycles.entry(innermost).or_default().0 += count;
+
+            // Recursive subroutines are only charged once per stack
+            let mut seen: Vec<u16> = Vec::new();
+            for &entry in [START_ADDR].iter().chain(stack) {
+                if !seen.contains(&entry) {
+                    seen.push(entry);
+                    cycles.entry(entry).or_default().1 += count;
+                }
+            }
+        }
+        cycles
+    }
+
+    /// A report of the hottest instructions, opcode classes and subroutines.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let total = self.total.max(1) as f64;
+        let percent = |count: u64| 100.0 * count as f64 / total;
+        let _ = writeln!(out, "{} instructions", self.total);
+
+        let mut hot: Vec<usize> = (0..RAM_SIZE).filter(|&pc| self.counts[pc] > 0).collect();
+        hot.sort_by_key(|&pc| std::cmp::Reverse(self.counts[pc]));
+        let _ = writeln!(out, "\nHottest instructions:");
+        for &pc in hot.iter().take(REPORT_ROWS) {
+            let count = self.counts[pc];
+            let op = self.opcodes[pc];
+            let _ = writeln!(
+                out,
+                "  {:03X}  {:>12}  {:5.1}%  {}",
+                pc,
+                count,
+                percent(count),
+                disassemble(op)
+            );
+        }
+
+        let mut classes: Vec<_> = self.classes.iter().collect();
+        classes.sort_by_key(|(_, &count)| std::cmp::Reverse(count));
+        let _ = writeln!(out, "\nOpcode classes:");
+        for (class, &count) in classes.into_iter().take(REPORT_ROWS) {
+            let _ = writeln!(out, "  {:<6} {:>12}  {:5.1}%", class, count, percent(count));
+        }
+
+        let mut subroutines: Vec<_> = self.subroutines().into_iter().collect();
+        subroutines.sort_by_key(|(_, (_, inclusive))| std::cmp::Reverse(*inclusive));
+        let _ = writeln!(out, "\nSubroutines (self, with callees):");
+        for (entry, (own, inclusive)) in subroutines.into_iter().take(REPORT_ROWS) {
+            let _ = writeln!(
+                out,
+                "  {:<8} {:>12}  {:5.1}%  {:>12}  {:5.1}%",
+                frame_name(entry),
+                own,
+                percent(own),
+                inclusive,
+                percent(inclusive)
+            );
+        }
+        out
+    }
+
+    /// The call stacks in the folded format of flamegraph.pl and inferno, one stack per line
+    /// with its instruction count, e.g. `main;sub_2A4;sub_310 1234`.
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| {
+                let mut line = frame_name(START_ADDR);
+                for &entry in stack {
+                    line.push(';');
+                    line.push_str(&frame_name(entry));
+                }
+                format!("{} {}", line, count)
+            })
+            .collect();
+        lines.sort();
+        lines.join("\n") + "\n"
+    }
+}
+
+fn frame_name(entry: u16) -> String {
+    match entry {
+        START_ADDR => "main".to_string(),
+        _ => format!("sub_{:03X}", entry),
+    }
+}
@@ -0,0 +1,11 @@
+/// Behaviours that differ between CHIP-8 interpreters. Every quirk is off by default, matching
+/// how chip-r has always run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// DXYN waits for the vertical blank, i.e. the next `tick_timers` call, before drawing. This
+    /// limits games to one sprite per frame, as on the COSMAC VIP.
+    pub display_wait: bool,
+    /// FX0A returns only once a key is pressed and then released, instead of as soon as any
+    /// key is held. Keys held before the instruction started have to be pressed again.
+    pub key_release: bool,
+}
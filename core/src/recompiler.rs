@@ -0,0 +1,200 @@
+//! An execution backend for batch runs, translating basic blocks into chains of closures.
+//!
+//! Each block is decoded once into one closure per instruction, calling the same handlers as
+//! `Emulator::tick`, so running it skips the fetch and dispatch of the interpreter while giving
+//! the same results. A block is left as soon as the program counter goes anywhere but the next
+//! instruction: a jump, a taken skip, or an instruction stalled by `DRAW` or `WKP`.
+//!
+//! Blocks are checked against the emulator's decoded instructions whenever a write landed on
+//! decoded code since they last ran, and recompiled if their bytes changed. Code rewritten over
+//! and over, like a sprite index patched every frame, is left to the interpreter instead. Blocks
+//! belong to one emulator: given another, the recompiler starts over.
+
+use crate::*;
+
+type Op = Box<dyn Fn(&mut Emulator) + Send + Sync>;
+
+// Instructions translated at most per block
+const MAX_BLOCK_LENGTH: usize = 64;
+// Times the code at an address may be rewritten before it's only interpreted
+const MAX_REWRITES: u8 = 4;
+
+struct Block {
+    instructions: Vec<Instruction>,
+    ops: Vec<Op>,
+    // Emulator::writes when the block was last known to match RAM
+    checked: u32,
+}
+
+pub struct Recompiler {
+    blocks: Vec<Option<Block>>,
+    rewrites: Vec<u8>,
+    // Emulator::id of the emulator the blocks were compiled from
+    emulator: Option<u64>,
+}
+
+impl Default for Recompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recompiler {
+    pub fn new() -> Self {
+        Self {
+            blocks: (0..RAM_SIZE).map(|_| None).collect(),
+            rewrites: vec![0; RAM_SIZE],
+            emulator: None,
+        }
+    }
+
+    /// Runs `ticks` instructions, exactly like calling `Emulator::tick` as many times.
+    pub fn run(&mut self, emu: &mut Emulator, ticks: usize) {
+        if self.emulator != Some(emu.id) {
+            self.clear();
+            self.emulator = Some(emu.id);
+        }
+
+        let mut remaining = ticks;
+        while remaining > 0 {
+            let pc = emu.pc as usize;
+            let Some(block) = self.block(emu, pc) else {
+                // Out of RAM, an unknown opcode or rewritten code: the interpreter deals with it
+                emu.tick();
+                remaining -= 1;
+                continue;
+            };
+
+            let writes = emu.writes;
+            for op in block.ops.iter().take(remaining) {
+                let next = emu.pc + 2;
+                emu.pc = next;
+                op(emu);
+                remaining -= 1;
+                // The instruction may have rewritten the rest of the block
+                if emu.pc != next || emu.writes != writes {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Whether the code at `address` runs from a translated block, rather than the interpreter.
+    pub fn is_compiled(&self, address: u16) -> bool {
+        self.blocks
+            .get(address as usize)
+            .is_some_and(Option::is_some)
+    }
+
+    /// Drops every translated block, e.g. after loading another ROM.
+    pub fn clear(&mut self) {
+        self.blocks.iter_mut().for_each(|block| *block = None);
+        self.rewrites.fill(0);
+    }
+
+    // The block starting at pc, compiled if needed; None if it should be interpreted
+    fn block(&mut self, emu: &mut Emulator, pc: usize) -> Option<&Block> {
+        if pc >= RAM_SIZE || self.rewrites[pc] >= MAX_REWRITES {
+            return None;
+        }
+
+        if let Some(block) = &mut self.blocks[pc] {
+            if block.checked != emu.writes {
+                let unchanged = block
+                    .instructions
+                    .iter()
+                    .enumerate()
+                    .all(|(i, &instruction)| emu.decoded[pc + 2 * i] == Some(instruction));
+                if !unchanged {
+                    self.blocks[pc] = None;
+                    self.rewrites[pc] += 1;
+                    if self.rewrites[pc] >= MAX_REWRITES {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        if self.blocks[pc].is_none() {
+            self.blocks[pc] = compile(emu, pc);
+        }
+        let block = self.blocks[pc].as_mut()?;
+        block.checked = emu.writes;
+        Some(block)
+    }
+}
+
+// Decodes the instructions from pc up to the first one that can't fall through, and records
+// them in the emulator's cache so writes to their bytes clear them
+fn compile(emu: &mut Emulator, pc: usize) -> Option<Block> {
+    let mut instructions = Vec::new();
+    let mut address = pc;
+    while instructions.len() < MAX_BLOCK_LENGTH && address + 1 < RAM_SIZE {
+        let op = ((emu.ram[address] as u16) << 8) | emu.ram[address + 1] as u16;
+        let Ok(instruction) = decode(op) else {
+            break;
+        };
+        emu.decoded[address] = Some(instruction);
+        instructions.push(instruction);
+        address += 2;
+
+        use Instruction::*;
+        if matches!(instruction, Jmp(_) | Jmp2(_) | Call(_) | Ret) {
+            break;
+        }
+    }
+
+    if instructions.is_empty() {
+        return None;
+    }
+    Some(Block {
+        ops: instructions
+            .iter()
+            .map(|&instruction| translate(instruction))
+            .collect(),
+        instructions,
+        checked: emu.writes,
+    })
+}
+
+fn translate(instruction: Instruction) -> Op {
+    use Instruction::*;
+
+    match instruction {
+        Nop => Box::new(|_| ()),
+        Clear => Box::new(|emu| emu.clear()),
+        Ret => Box::new(|emu| emu.ret()),
+        Jmp(address) => Box::new(move |emu| emu.jmp(address)),
+        Call(address) => Box::new(move |emu| emu.call(address)),
+        Seq(x, value) => Box::new(move |emu| emu.seq(x, value)),
+        Snq(x, value) => Box::new(move |emu| emu.snq(x, value)),
+        Seqr(x, y) => Box::new(move |emu| emu.seqr(x, y)),
+        Ld(x, value) => Box::new(move |emu| emu.ld(x, value)),
+        Addiw(x, value) => Box::new(move |emu| emu.addiw(x, value)),
+        Mv(x, y) => Box::new(move |emu| emu.mv(x, y)),
+        Or(x, y) => Box::new(move |emu| emu.or(x, y)),
+        And(x, y) => Box::new(move |emu| emu.and(x, y)),
+        Xor(x, y) => Box::new(move |emu| emu.xor(x, y)),
+        Add(x, y) => Box::new(move |emu| emu.add(x, y)),
+        Sub(x, y) => Box::new(move |emu| emu.sub(x, y)),
+        Shr(x, _) => Box::new(move |emu| emu.shr(x)),
+        Sub2(x, y) => Box::new(move |emu| emu.sub2(x, y)),
+        Shl(x, _) => Box::new(move |emu| emu.shl(x)),
+        Snqr(x, y) => Box::new(move |emu| emu.snqr(x, y)),
+        Ldi(address) => Box::new(move |emu| emu.ldi(address)),
+        Jmp2(address) => Box::new(move |emu| emu.jmp2(address)),
+        Rnd(x, value) => Box::new(move |emu| emu.rnd(x, value)),
+        Draw(x, y, height) => Box::new(move |emu| emu.draw(x, y, height)),
+        Skp(x) => Box::new(move |emu| emu.skp(x)),
+        Snp(x) => Box::new(move |emu| emu.snp(x)),
+        Ldt(x) => Box::new(move |emu| emu.ldt(x)),
+        Wkp(x) => Box::new(move |emu| emu.wkp(x)),
+        Sdt(x) => Box::new(move |emu| emu.sdt(x)),
+        Sst(x) => Box::new(move |emu| emu.sst(x)),
+        Iadd(x) => Box::new(move |emu| emu.iadd(x)),
+        Ldf(x) => Box::new(move |emu| emu.ldf(x)),
+        Sbcd(x) => Box::new(move |emu| emu.sbcd(x)),
+        Strr(x) => Box::new(move |emu| emu.strr(x)),
+        Ldr(x) => Box::new(move |emu| emu.ldr(x)),
+    }
+}
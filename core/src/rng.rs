@@ -0,0 +1,27 @@
+/// Small xorshift generator used by RND when the `rand` feature is off. It's nowhere near
+/// cryptographic quality, but games only need something that doesn't look predictable.
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // xorshift gets stuck at zero forever
+        let state = if seed == 0 { 0x2545_F491 } else { seed };
+        Self { state }
+    }
+
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        // The high bits are the better mixed ones
+        (x >> 24) as u8
+    }
+}
@@ -0,0 +1,108 @@
+//! Detection of self-modifying code: writes to bytes that already ran as instructions, and
+//! instructions made of bytes the program wrote. Both are common in CHIP-8 games, e.g. patching
+//! the operand of an `LDI` to pick a sprite, but they're also a classic source of bugs.
+//!
+//! Like the profiler, `record` is called before every `Emulator::tick`.
+
+use crate::analysis::{disassemble, memory_access, Access};
+use crate::*;
+
+use std::collections::HashSet;
+use std::fmt;
+
+const EXECUTED: u8 = 1 << 0;
+const WRITTEN: u8 = 1 << 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// The instruction at `pc` wrote to `address`, which had run as code before.
+    CodeWritten,
+    /// The instruction at `pc` was made of bytes written by the program, `address` being the
+    /// first of them.
+    DataExecuted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Event {
+    pub kind: Kind,
+    pub pc: u16,
+    pub address: u16,
+    pub opcode: u16,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let instruction = disassemble(self.opcode);
+        match self.kind {
+            Kind::CodeWritten => write!(
+                f,
+                "{:03X}: {} wrote to {:03X}, which ran as code",
+                self.pc, instruction, self.address
+            ),
+            Kind::DataExecuted => write!(
+                f,
+                "{:03X}: {} ran from bytes written to {:03X}",
+                self.pc, instruction, self.address
+            ),
+        }
+    }
+}
+
+pub struct Detector {
+    flags: Vec<u8>,
+    seen: HashSet<(Kind, u16, u16)>,
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Self {
+            flags: vec![0; RAM_SIZE],
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Checks the instruction the emulator is about to run. Returns the events it causes, only
+    /// the first time each happens, so a loop patching its own code is reported once.
+    pub fn record(&mut self, emu: &Emulator) -> Vec<Event> {
+        let pc = emu.pc as usize;
+        if pc + 1 >= RAM_SIZE {
+            return Vec::new();
+        }
+        let op = ((emu.ram[pc] as u16) << 8) | emu.ram[pc + 1] as u16;
+        let mut events = Vec::new();
+
+        let written = (pc..pc + 2).find(|&address| self.flags[address] & WRITTEN != 0);
+        if let Some(address) = written {
+            events.push(event(Kind::DataExecuted, pc, address, op));
+        }
+        self.flags[pc] |= EXECUTED;
+        self.flags[pc + 1] |= EXECUTED;
+
+        if let Some(Access::Write(range)) = memory_access(op, emu.i_reg) {
+            for address in range {
+                if self.flags[address] & EXECUTED != 0 {
+                    events.push(event(Kind::CodeWritten, pc, address, op));
+                }
+                self.flags[address] |= WRITTEN;
+            }
+        }
+
+        events.retain(|event| self.seen.insert((event.kind, event.pc, event.address)));
+        events
+    }
+}
+
+fn event(kind: Kind, pc: usize, address: usize, opcode: u16) -> Event {
+    Event {
+        kind,
+        pc: pc as u16,
+        address: address as u16,
+        opcode,
+    }
+}
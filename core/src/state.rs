@@ -0,0 +1,184 @@
+use crate::*;
+
+// "C8RS" followed by a version byte, bumped whenever the layout below changes
+const MAGIC: [u8; 4] = *b"C8RS";
+const VERSION: u8 = 2;
+
+const HEADER_SIZE: usize = MAGIC.len() + 1;
+// Offset of the FX0A wait, checked before anything gets restored
+const KEY_WAIT_AT: usize = HEADER_SIZE
+    + 2 + 2 + 1 + 1 // pc, sp, dt, st
+    + RAM_SIZE
+    + SCREEN_WIDTH * SCREEN_HEIGHT
+    + 3 * NUM_KEYS; // keys and key edges
+
+/// Size in bytes of a save state produced by `Emulator::save_state`.
+pub const STATE_SIZE: usize = KEY_WAIT_AT + 1
+    + NUM_REGS + 2 // v_reg, i_reg
+    + 2 * STACK_SIZE
+    + 1 // vblank
+    + 4 // built-in rng
+    + 1; // seeded, whether RND uses the built-in rng rather than rand's entropy
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// The buffer is smaller than `STATE_SIZE`.
+    BufferTooSmall,
+    /// The data wasn't produced by `save_state`, or by an incompatible version of it. Also
+    /// returned by `set_cpu_state` for registers no program could reach.
+    Invalid,
+}
+
+impl Emulator {
+    /// Serializes the whole machine, except for its quirks, into the first `STATE_SIZE` bytes of
+    /// `buffer`. Doesn't allocate, so it works in no_std builds too.
+    pub fn save_state(&self, buffer: &mut [u8]) -> Result<(), StateError> {
+        if buffer.len() < STATE_SIZE {
+            return Err(StateError::BufferTooSmall);
+        }
+
+        let mut w = Writer { buffer, at: 0 };
+        w.bytes(&MAGIC);
+        w.u8(VERSION);
+        w.u16(self.pc);
+        w.u16(self.sp);
+        w.u8(self.dt);
+        w.u8(self.st);
+        w.bytes(&self.ram);
+        w.bools(&self.screen);
+        w.bools(&self.keys);
+        w.bools(&self.key_presses);
+        w.bools(&self.key_releases);
+        w.u8(match self.key_wait {
+            KeyWait::Idle => 0xFF,
+            KeyWait::Waiting => 0xFE,
+            KeyWait::Pressed(key) => key as u8,
+        });
+        w.bytes(&self.v_reg);
+        w.u16(self.i_reg);
+        for address in self.stack {
+            w.u16(address);
+        }
+        w.u8(self.vblank as u8);
+        w.bytes(&self.rng.state().to_le_bytes());
+        // Without rand, RND always uses the built-in rng
+        #[cfg(feature = "rand")]
+        w.u8(self.seeded as u8);
+        #[cfg(not(feature = "rand"))]
+        w.u8(1);
+
+        Ok(())
+    }
+
+    /// Restores a state saved by `save_state`. Nothing is changed if the state is rejected.
+    pub fn load_state(&mut self, buffer: &[u8]) -> Result<(), StateError> {
+        if buffer.len() < STATE_SIZE {
+            return Err(StateError::BufferTooSmall);
+        }
+        if buffer[..MAGIC.len()] != MAGIC || buffer[MAGIC.len()] != VERSION {
+            return Err(StateError::Invalid);
+        }
+
+        let mut r = Reader {
+            buffer,
+            at: HEADER_SIZE,
+        };
+        let pc = r.u16();
+        let sp = r.u16();
+        if pc as usize >= RAM_SIZE || sp as usize > STACK_SIZE {
+            return Err(StateError::Invalid);
+        }
+        let key_wait = match buffer[KEY_WAIT_AT] {
+            0xFF => KeyWait::Idle,
+            0xFE => KeyWait::Waiting,
+            key if (key as usize) < NUM_KEYS => KeyWait::Pressed(key as usize),
+            _ => return Err(StateError::Invalid),
+        };
+
+        self.pc = pc;
+        self.sp = sp;
+        self.fault = None;
+        self.dt = r.u8();
+        self.st = r.u8();
+        r.bytes(&mut self.ram);
+        self.ram_version = self.ram_version.wrapping_add(1);
+        self.invalidate(0..RAM_SIZE);
+        r.bools(&mut self.screen);
+        r.bools(&mut self.keys);
+        r.bools(&mut self.key_presses);
+        r.bools(&mut self.key_releases);
+        r.u8(); // key_wait, already parsed above
+        self.key_wait = key_wait;
+        r.bytes(&mut self.v_reg);
+        self.i_reg = r.u16();
+        for address in self.stack.iter_mut() {
+            *address = r.u16();
+        }
+        self.vblank = r.u8() != 0;
+        let mut rng = [0; 4];
+        r.bytes(&mut rng);
+        self.rng = Rng::new(u32::from_le_bytes(rng));
+        #[cfg(feature = "rand")]
+        {
+            self.seeded = r.u8() != 0;
+        }
+
+        self.dirty = Some(DirtyRect::full_screen());
+        Ok(())
+    }
+}
+
+struct Writer<'a> {
+    buffer: &'a mut [u8],
+    at: usize,
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, data: &[u8]) {
+        self.buffer[self.at..self.at + data.len()].copy_from_slice(data);
+        self.at += data.len();
+    }
+
+    fn bools(&mut self, data: &[bool]) {
+        for &value in data {
+            self.u8(value as u8);
+        }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+}
+
+struct Reader<'a> {
+    buffer: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, data: &mut [u8]) {
+        data.copy_from_slice(&self.buffer[self.at..self.at + data.len()]);
+        self.at += data.len();
+    }
+
+    fn bools(&mut self, data: &mut [bool]) {
+        for value in data.iter_mut() {
+            *value = self.u8() != 0;
+        }
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.at += 1;
+        self.buffer[self.at - 1]
+    }
+
+    fn u16(&mut self) -> u16 {
+        let mut bytes = [0; 2];
+        self.bytes(&mut bytes);
+        u16::from_le_bytes(bytes)
+    }
+}
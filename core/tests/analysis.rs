@@ -0,0 +1,136 @@
+use core::analysis::{disassemble, Block, Cfg, Edge};
+
+#[rustfmt::skip]
+const ROM: [u8; 19] = [
+    0x30, 0x05, // 200: SEQ V0, 0x05
+    0x12, 0x08, // 202: JMP 0x208
+    0x22, 0x0E, // 204: CALL 0x20E
+    0xB2, 0x10, // 206: JMP2 0x210
+    0x00, 0xE0, // 208: CLEAR
+    0x12, 0x0A, // 20A: JMP 0x20A
+    0xAA, 0xBB, // 20C: never reached
+    0x60, 0x01, // 20E: LD V0, 0x01
+    0x00, 0xEE, // 210: RET
+    0xFF,       // 212: never reached
+];
+
+fn block(start: u16, end: u16, successors: &[(u16, Edge)], calls: &[u16]) -> Block {
+    Block {
+        start,
+        end,
+        successors: successors.to_vec(),
+        calls: calls.to_vec(),
+    }
+}
+
+#[test]
+fn splits_blocks_at_changes_of_flow() {
+    let cfg = Cfg::new(&ROM);
+    let blocks: Vec<_> = cfg.blocks.values().cloned().collect();
+    assert_eq!(
+        blocks,
+        [
+            block(
+                0x200,
+                0x202,
+                &[(0x202, Edge::Next), (0x204, Edge::Skip)],
+                &[]
+            ),
+            block(0x202, 0x204, &[(0x208, Edge::Jump)], &[]),
+            block(0x204, 0x208, &[], &[0x20E]),
+            block(0x208, 0x20A, &[(0x20A, Edge::Next)], &[]),
+            block(0x20A, 0x20C, &[(0x20A, Edge::Jump)], &[]),
+            block(0x20E, 0x212, &[], &[]),
+        ]
+    );
+}
+
+#[test]
+fn finds_subroutines_and_unresolved_jumps() {
+    let cfg = Cfg::new(&ROM);
+    assert_eq!(cfg.subroutines.iter().copied().collect::<Vec<_>>(), [0x20E]);
+    assert_eq!(cfg.unresolved, [0x206]);
+    assert!(cfg.invalid.is_empty());
+}
+
+#[test]
+fn unreached_bytes_are_data() {
+    let cfg = Cfg::new(&ROM);
+    let code: Vec<_> = cfg.instructions().collect();
+    assert_eq!(
+        code,
+        [0x200, 0x202, 0x204, 0x206, 0x208, 0x20A, 0x20E, 0x210]
+    );
+    assert!(cfg.is_code(0x20B) && cfg.is_code(0x211));
+    assert!(!cfg.is_code(0x20C) && !cfg.is_code(0x20D) && !cfg.is_code(0x212));
+}
+
+#[test]
+fn stops_at_unknown_opcodes() {
+    let cfg = Cfg::new(&[0x60, 0x01, 0x01, 0x23, 0x60, 0x02]);
+    assert_eq!(cfg.invalid, [0x202]);
+    assert_eq!(cfg.instructions().collect::<Vec<_>>(), [0x200, 0x202]);
+}
+
+#[test]
+fn skips_at_the_end_of_ram_have_no_successors_past_it() {
+    // NOPs up to a SEQ in the last word of RAM
+    let mut rom = vec![0; 0xE00];
+    rom[0xDFE] = 0x30;
+    let cfg = Cfg::new(&rom);
+    assert_eq!(cfg.blocks.len(), 1);
+    assert_eq!(cfg.blocks[&0x200].end, 0x1000);
+    assert!(cfg.blocks[&0x200].successors.is_empty());
+}
+
+#[test]
+fn listing() {
+    let expected = "\
+loc_200:
+  200  3005  SEQ V0, 0x05
+loc_202:
+  202  1208  JMP 0x208
+loc_204:
+  204  220E  CALL 0x20E
+  206  B210  JMP2 0x210
+loc_208:
+  208  00E0  CLEAR
+loc_20A:
+  20A  120A  JMP 0x20A
+  20C  AA    DB 0xAA
+  20D  BB    DB 0xBB
+
+sub_20E:
+  20E  6001  LD V0, 0x01
+  210  00EE  RET
+  212  FF    DB 0xFF
+";
+    assert_eq!(Cfg::new(&ROM).listing(&ROM), expected);
+}
+
+#[test]
+fn dot() {
+    let expected = r#"digraph cfg {
+  node [shape=box, fontname=monospace];
+  b200 [label="200  SEQ V0, 0x05\l"];
+  b200 -> b202;
+  b200 -> b204 [label="skip"];
+  b202 [label="202  JMP 0x208\l"];
+  b202 -> b208 [label="jmp"];
+  b204 [label="204  CALL 0x20E\l206  JMP2 0x210\l", color=red];
+  b204 -> b20E [style=dashed, label="call"];
+  b208 [label="208  CLEAR\l"];
+  b208 -> b20A;
+  b20A [label="20A  JMP 0x20A\l"];
+  b20A -> b20A [label="jmp"];
+  b20E [label="20E  LD V0, 0x01\l210  RET\l", peripheries=2];
+}
+"#;
+    assert_eq!(Cfg::new(&ROM).to_dot(&ROM), expected);
+}
+
+#[test]
+fn disassembles_unknown_opcodes_as_words() {
+    assert_eq!(disassemble(0xD125), "DRAW V1, V2, 5");
+    assert_eq!(disassemble(0x0123), "DW 0x0123");
+}
@@ -0,0 +1,84 @@
+//! RAM searches narrowed over several snapshots, and frozen bytes.
+
+use core::cheat::{Comparison, Freezer, RamSearch};
+use core::*;
+
+const BYTES: usize = 0x300;
+
+// Starts a search with 5s at BYTES, then leaves the first one alone, increases the second,
+// decreases the third and sets the fourth to 0x2A
+fn search_after_changes() -> (Emulator, RamSearch) {
+    let mut emu = Emulator::new();
+    emu.write_memory(BYTES, &[5, 5, 5, 5]);
+    let search = RamSearch::new(&emu);
+    emu.write_memory(BYTES, &[5, 9, 1, 0x2A]);
+    (emu, search)
+}
+
+fn filtered(comparison: Comparison) -> Vec<usize> {
+    let (emu, mut search) = search_after_changes();
+    search.filter(&emu, comparison);
+    search.candidates().collect()
+}
+
+#[test]
+fn starts_with_every_address() {
+    let (_, search) = search_after_changes();
+    assert_eq!(search.count(), RAM_SIZE);
+}
+
+#[test]
+fn keeps_the_addresses_matching_each_comparison() {
+    let unchanged = filtered(Comparison::Unchanged);
+    assert_eq!(unchanged.len(), RAM_SIZE - 3);
+    assert!(unchanged.contains(&BYTES));
+    assert!(!unchanged
+        .iter()
+        .any(|address| (BYTES + 1..BYTES + 4).contains(address)));
+
+    assert_eq!(
+        filtered(Comparison::Changed),
+        [BYTES + 1, BYTES + 2, BYTES + 3]
+    );
+    assert_eq!(filtered(Comparison::Increased), [BYTES + 1, BYTES + 3]);
+    assert_eq!(filtered(Comparison::Decreased), [BYTES + 2]);
+    assert_eq!(filtered(Comparison::EqualTo(0x2A)), [BYTES + 3]);
+}
+
+#[test]
+fn compares_with_the_previous_filter() {
+    let (mut emu, mut search) = search_after_changes();
+    search.filter(&emu, Comparison::Changed);
+
+    // Nothing moved since the last snapshot
+    search.filter(&emu, Comparison::Unchanged);
+    assert_eq!(search.count(), 3);
+
+    emu.write_memory(BYTES + 1, &[10]);
+    emu.write_memory(BYTES + 2, &[0]);
+    search.filter(&emu, Comparison::Increased);
+    assert_eq!(search.candidates().collect::<Vec<_>>(), [BYTES + 1]);
+
+    // Candidates that were dropped stay dropped
+    emu.write_memory(BYTES + 2, &[7]);
+    search.filter(&emu, Comparison::Changed);
+    assert_eq!(search.count(), 0);
+}
+
+#[test]
+fn freezer_rewrites_frozen_bytes() {
+    let mut emu = Emulator::new();
+    let mut freezer = Freezer::new();
+    freezer.freeze(BYTES, 3);
+    freezer.freeze(BYTES + 1, 9);
+    freezer.unfreeze(BYTES + 1);
+
+    emu.write_memory(BYTES, &[1, 1]);
+    freezer.apply(&mut emu);
+    assert_eq!(emu.memory(BYTES..BYTES + 2), [3, 1]);
+
+    freezer.clear();
+    emu.write_memory(BYTES, &[1]);
+    freezer.apply(&mut emu);
+    assert_eq!(emu.memory(BYTES..BYTES + 1), [1]);
+}
@@ -0,0 +1,71 @@
+use core::coverage::Coverage;
+use core::*;
+
+#[rustfmt::skip]
+const ROM: [u8; 21] = [
+    0xA2, 0x10, // 200: LDI 0x210
+    0x30, 0x00, // 202: SEQ V0, 0x00, taken
+    0x00, 0xE0, // 204: CLEAR, skipped
+    0x40, 0x00, // 206: SNQ V0, 0x00, not taken
+    0xD0, 0x02, // 208: DRAW V0, V0, 2, reading 210 and 211
+    0xA2, 0x14, // 20A: LDI 0x214
+    0xF0, 0x55, // 20C: STRR V0, writing 214
+    0x12, 0x0E, // 20E: JMP 0x20E
+    0xF0, 0x90, // 210: sprite
+    0x55, 0x66, // 212: never accessed
+    0x00,       // 214: written
+];
+
+fn coverage() -> Coverage {
+    let mut emu = Emulator::new();
+    emu.load(&ROM);
+    let mut coverage = Coverage::new();
+    for _ in 0..10 {
+        coverage.record(&emu);
+        emu.tick();
+    }
+    coverage
+}
+
+#[test]
+fn marks_executed_read_and_written_bytes() {
+    let coverage = coverage();
+    let executed: Vec<_> = (0x200..0x215)
+        .filter(|&a| coverage.is_executed(a))
+        .collect();
+    let expected: Vec<_> = (0x200..0x204).chain(0x206..0x210).collect();
+    assert_eq!(executed, expected);
+
+    let read: Vec<_> = (0..RAM_SIZE).filter(|&a| coverage.is_read(a)).collect();
+    assert_eq!(read, [0x210, 0x211]);
+    let written: Vec<_> = (0..RAM_SIZE).filter(|&a| coverage.is_written(a)).collect();
+    assert_eq!(written, [0x214]);
+}
+
+#[test]
+fn annotated_listing() {
+    let expected = "\
+loc_200:
+       200  A210  LDI 0x210
+       202  3000  SEQ V0, 0x00
+loc_204:
+#####  204  00E0  CLEAR
+loc_206:
+       206  4000  SNQ V0, 0x00
+loc_208:
+       208  D002  DRAW V0, V0, 2
+loc_20A:
+       20A  A214  LDI 0x214
+       20C  F055  STRR V0
+loc_20E:
+       20E  120E  JMP 0x20E
+    R  210  F0    DB 0xF0
+    R  211  90    DB 0x90
+    -  212  55    DB 0x55
+    -  213  66    DB 0x66
+    W  214  00    DB 0x00
+
+7 of 8 instructions executed (87.5%), 2 bytes read, 1 bytes written
+";
+    assert_eq!(coverage().annotated_listing(&ROM), expected);
+}
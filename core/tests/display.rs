@@ -0,0 +1,106 @@
+//! The dirty rectangle reported after drawing, and the display wait quirk.
+
+use core::*;
+
+// Draws the font's 0, 4 pixels wide and 5 high, at (x, y), stopping before the CLEAR after it
+fn draw_zero_at(x: u8, y: u8) -> Emulator {
+    let mut emu = Emulator::new();
+    #[rustfmt::skip]
+    emu.load(&[
+        0xA0, 0x00, // LDI 0x000, the font's 0
+        0x60, x,    // LD V0, x
+        0x61, y,    // LD V1, y
+        0xD0, 0x15, // DRAW V0, V1, 5
+        0x00, 0xE0, // CLEAR
+    ]);
+    assert!(emu.display_changed(), "a new screen has to be drawn once");
+    for _ in 0..4 {
+        emu.tick();
+    }
+    emu
+}
+
+fn rect(x: usize, y: usize, width: usize, height: usize) -> Option<DirtyRect> {
+    Some(DirtyRect {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+#[test]
+fn reports_the_area_a_sprite_touched() {
+    let mut emu = draw_zero_at(60, 27);
+    assert_eq!(emu.take_dirty_rect(), rect(60, 27, 4, 5));
+    // Reading it resets it
+    assert_eq!(emu.take_dirty_rect(), None);
+    assert!(!emu.display_changed());
+}
+
+#[test]
+fn sprites_wrapping_around_span_the_screen() {
+    let mut emu = draw_zero_at(62, 30);
+    assert_eq!(
+        emu.take_dirty_rect(),
+        rect(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT)
+    );
+
+    let mut emu = draw_zero_at(62, 3);
+    assert_eq!(emu.take_dirty_rect(), rect(0, 3, SCREEN_WIDTH, 5));
+}
+
+#[test]
+fn clearing_reports_the_whole_screen() {
+    let mut emu = draw_zero_at(10, 10);
+    assert!(emu.display_changed());
+    emu.tick();
+    assert_eq!(
+        emu.take_dirty_rect(),
+        rect(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT)
+    );
+    assert!(!emu.display_changed());
+}
+
+// Runs two draws in a row and returns the pc after each tick, ending a frame every `frame` ticks
+fn pcs_drawing_twice(display_wait: bool, frame: usize) -> Vec<u16> {
+    let mut emu = Emulator::new();
+    emu.set_quirks(Quirks {
+        display_wait,
+        key_release: false,
+    });
+    #[rustfmt::skip]
+    emu.load(&[
+        0xD0, 0x15, // DRAW V0, V0, 5
+        0xD0, 0x15, // DRAW V0, V0, 5
+        0x12, 0x04, // JMP 0x204
+    ]);
+
+    let mut pcs = Vec::new();
+    for tick in 1..=6 {
+        emu.tick();
+        pcs.push(emu.pc());
+        if tick % frame == 0 {
+            emu.tick_timers();
+        }
+    }
+    pcs
+}
+
+#[test]
+fn display_wait_draws_once_per_frame() {
+    // Nothing waits without the quirk
+    assert_eq!(
+        pcs_drawing_twice(false, 3),
+        [0x202, 0x204, 0x204, 0x204, 0x204, 0x204]
+    );
+    // The first draw waits for the first frame to end, and the second for the next one
+    assert_eq!(
+        pcs_drawing_twice(true, 3),
+        [0x200, 0x200, 0x200, 0x202, 0x202, 0x202]
+    );
+    assert_eq!(
+        pcs_drawing_twice(true, 2),
+        [0x200, 0x200, 0x202, 0x202, 0x204, 0x204]
+    );
+}
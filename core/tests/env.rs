@@ -0,0 +1,90 @@
+use core::env::{self, Env, Game, InvalidAction, Observation};
+use core::*;
+
+// Stores a random byte at 0x300, and counts at 0x302 the loops key 1 is held for. A loop runs 7
+// instructions, one frame at that speed.
+#[rustfmt::skip]
+const COUNTER: [u8; 14] = [
+    0xA3, 0x00, // 200: LDI 0x300
+    0xC0, 0xFF, // 202: RND V0, 0xFF
+    0x61, 0x01, // 204: LD V1, 1
+    0xE1, 0xA1, // 206: SNP V1
+    0x72, 0x01, // 208: ADDIW V2, 1
+    0xF2, 0x55, // 20A: STRR V2
+    0x12, 0x00, // 20C: JMP 0x200
+];
+
+// Action 2 holds key 1, see Game::new
+const HOLD: usize = 2;
+
+fn counter_env() -> Env {
+    let mut game = Game::new(&COUNTER);
+    game.ticks_per_frame = 7;
+    game.reward = env::increase(0x302);
+    game.done = env::equals(0x302, 20);
+    Env::new(game, Observation::Ram, 4)
+}
+
+fn play(env: &mut Env, seed: u32, actions: &[usize]) -> Vec<(Vec<u8>, f64, bool)> {
+    let mut steps = vec![(env.reset(seed).to_vec(), 0.0, false)];
+    for &action in actions {
+        let (observation, reward, done) = env.step(action).unwrap();
+        steps.push((observation.to_vec(), reward, done));
+    }
+    steps
+}
+
+#[test]
+fn episodes_replay_for_the_same_seed_and_actions() {
+    let actions = [0, HOLD, 5, HOLD, 0, 0, HOLD, 16, HOLD];
+    let mut first = counter_env();
+    let mut second = counter_env();
+    let played = play(&mut first, 7, &actions);
+    assert!(play(&mut second, 7, &actions) == played);
+    assert!(
+        play(&mut first, 7, &actions) == played,
+        "reset didn't start over"
+    );
+    assert!(play(&mut first, 8, &actions) != played);
+}
+
+#[test]
+fn rewards_and_done_follow_the_ram() {
+    let mut env = counter_env();
+    env.reset(0);
+    for step in 1..=5 {
+        let (ram, reward, done) = env.step(HOLD).unwrap();
+        // 4 frames
+        assert_eq!(reward, 4.0);
+        assert_eq!(ram[0x302], 4 * step);
+        assert_eq!(done, step == 5);
+    }
+    // The episode is over, so frames don't run anymore
+    assert_eq!(env.step(HOLD).unwrap().1, 0.0);
+
+    env.reset(0);
+    let (ram, reward, done) = env.step(0).unwrap();
+    assert_eq!((ram[0x302], reward, done), (0, 0.0, false));
+}
+
+#[test]
+fn screen_observations_have_a_byte_per_pixel() {
+    let mut game = Game::new(&[0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04]); // draws the 0 of the font
+    game.ticks_per_frame = 3;
+    let mut env = Env::new(game, Observation::Screen, 1);
+    env.reset(0);
+    let (screen, _, _) = env.step(0).unwrap();
+    assert_eq!(screen.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+    assert_eq!(screen[..5], [1, 1, 1, 1, 0]);
+}
+
+#[test]
+fn rejects_unknown_actions() {
+    let mut env = counter_env();
+    assert_eq!(env.actions(), 1 + NUM_KEYS);
+    let before = env.reset(3).to_vec();
+    assert_eq!(env.step(env.actions()).err(), Some(InvalidAction(17)));
+    assert_eq!(env.step(usize::MAX).err(), Some(InvalidAction(usize::MAX)));
+    assert_eq!(env.emulator().pc(), 0x200);
+    assert!(env.step(0).unwrap().0 != before);
+}
@@ -0,0 +1,207 @@
+//! Drives the GDB stub through a local TCP client, the way a debugger would.
+
+use core::gdb::GdbStub;
+use core::*;
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+// LD V0, 0x2A; JMP 0x200
+const LOOP: [u8; 4] = [0x60, 0x2A, 0x12, 0x00];
+// LD V0, 1; ADD V0, 1; JMP 0x202
+const COUNTER: [u8; 6] = [0x60, 0x01, 0x70, 0x01, 0x12, 0x02];
+// WKP V0
+const WAIT_KEY: [u8; 2] = [0xF0, 0x0A];
+
+struct Client {
+    stub: GdbStub,
+    emu: Emulator,
+    stream: TcpStream,
+    received: Vec<u8>,
+}
+
+impl Client {
+    fn connect(rom: &[u8]) -> Self {
+        let stub = GdbStub::bind(0).unwrap();
+        let stream = TcpStream::connect(stub.local_addr().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let mut emu = Emulator::new();
+        emu.load(rom);
+        Self {
+            stub,
+            emu,
+            stream,
+            received: Vec::new(),
+        }
+    }
+
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", data, checksum).unwrap();
+    }
+
+    // Ctrl-C, sent outside of a packet
+    fn interrupt(&mut self) {
+        self.stream.write_all(&[0x03]).unwrap();
+    }
+
+    // Runs the stub and the emulator for a while, giving packets sent before time to arrive
+    fn run(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.stub.poll(&mut self.emu).unwrap();
+            self.stub.tick(&mut self.emu).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    // Runs the stub and the emulator until a packet arrives, and returns its data
+    fn reply(&mut self) -> String {
+        for _ in 0..500 {
+            self.stub.poll(&mut self.emu).unwrap();
+            self.stub.tick(&mut self.emu).unwrap();
+
+            let mut buffer = [0; 1024];
+            match self.stream.read(&mut buffer) {
+                Ok(n) => self.received.extend_from_slice(&buffer[..n]),
+                // Nothing yet
+                Err(error)
+                    if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(error) => panic!("{}", error),
+            }
+
+            // Acks
+            while self.received.first() == Some(&b'+') {
+                self.received.remove(0);
+            }
+            if let Some(end) = self.received.iter().position(|&byte| byte == b'#') {
+                if self.received.len() >= end + 3 {
+                    assert_eq!(self.received[0], b'$');
+                    let data = String::from_utf8_lossy(&self.received[1..end]).into_owned();
+                    self.received.drain(..end + 3);
+                    return data;
+                }
+            }
+        }
+        panic!(
+            "no reply, got {:?}",
+            String::from_utf8_lossy(&self.received)
+        );
+    }
+
+    // Sends a packet and returns the data of the reply
+    fn request(&mut self, data: &str) -> String {
+        self.send(data);
+        self.reply()
+    }
+}
+
+#[test]
+fn reads_and_writes_memory_and_registers() {
+    let mut client = Client::connect(&LOOP);
+    assert_eq!(client.request("?"), "S05");
+    assert_eq!(client.request("m200,4"), "602a1200");
+    assert_eq!(client.request("M300,2:abcd"), "OK");
+    assert_eq!(client.request("m2ff,4"), "00abcd00");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p0"), "2a");
+    assert_eq!(client.request("p11"), "0202");
+    assert_eq!(
+        client.request("qXfer:features:read:target.xml:0,5"),
+        "m<?xml"
+    );
+}
+
+#[test]
+fn rejects_ranges_outside_ram() {
+    let mut client = Client::connect(&LOOP);
+    assert_eq!(client.request("mfff,1"), "00");
+    assert_eq!(client.request("mfff,2"), "E01");
+    assert_eq!(client.request("mffffffffffffffff,2"), "E01");
+    assert_eq!(client.request("Mffffffffffffffff,2:abcd"), "E01");
+    assert_eq!(client.request("Mfff,2:abcd"), "E01");
+    assert_eq!(client.request("M200,2:ab"), "E01");
+    assert!(client
+        .request("qXfer:features:read:target.xml:ffffffffffffffff,2")
+        .starts_with('l'));
+    assert!(client
+        .request("qXfer:features:read:target.xml:1,ffffffffffffffff")
+        .starts_with("l?xml"));
+
+    // The stub is still serving
+    assert_eq!(client.request("m200,2"), "602a");
+}
+
+#[test]
+fn stops_at_breakpoints_until_removed() {
+    let mut client = Client::connect(&COUNTER);
+    assert_eq!(client.request("Z0,204,2"), "OK");
+
+    client.send("c");
+    assert_eq!(client.reply(), "S05");
+    assert_eq!(client.request("p11"), "0402");
+    assert_eq!(client.request("p0"), "02");
+
+    // Resuming runs the instruction at the breakpoint, and stops there on the next lap
+    client.send("c");
+    assert_eq!(client.reply(), "S05");
+    assert_eq!(client.request("p11"), "0402");
+    assert_eq!(client.request("p0"), "03");
+
+    assert_eq!(client.request("z0,204,2"), "OK");
+    client.send("c");
+    client.run(50);
+    client.interrupt();
+    assert_eq!(client.reply(), "S02");
+    assert!(client.stub.is_halted());
+}
+
+#[test]
+fn steps_one_instruction() {
+    let mut client = Client::connect(&COUNTER);
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0202");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0402");
+    assert_eq!(client.request("p0"), "02");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0202");
+}
+
+#[test]
+fn does_not_stop_again_at_a_stalled_breakpoint() {
+    let mut client = Client::connect(&WAIT_KEY);
+    assert_eq!(client.request("Z0,200,2"), "OK");
+
+    // The key wait holds the pc on the breakpoint, which only Ctrl-C interrupts
+    client.send("c");
+    client.run(50);
+    client.interrupt();
+    assert_eq!(client.reply(), "S02");
+    assert_eq!(client.request("p11"), "0002");
+}
+
+#[test]
+fn resumes_when_the_debugger_disconnects() {
+    let Client {
+        mut stub,
+        mut emu,
+        stream,
+        ..
+    } = Client::connect(&LOOP);
+    stub.poll(&mut emu).unwrap();
+    assert!(stub.is_halted());
+
+    drop(stream);
+    for _ in 0..500 {
+        stub.poll(&mut emu).unwrap();
+        if !stub.is_halted() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("still halted");
+}
@@ -0,0 +1,58 @@
+use core::*;
+
+#[test]
+fn cpu_state_round_trips() {
+    let mut emu = Emulator::new();
+    let mut state = emu.cpu_state();
+    state.pc = 0x300;
+    state.sp = STACK_SIZE as u16;
+    state.i = 0x123;
+    state.v[0xF] = 1;
+    state.stack[0] = 0x202;
+    emu.set_cpu_state(&state).unwrap();
+    assert!(emu.cpu_state() == state);
+}
+
+#[test]
+fn set_cpu_state_rejects_unreachable_registers() {
+    let mut emu = Emulator::new();
+    let before = emu.cpu_state();
+
+    let mut state = before;
+    state.sp = STACK_SIZE as u16 + 1;
+    assert_eq!(emu.set_cpu_state(&state), Err(StateError::Invalid));
+    let mut state = before;
+    state.pc = RAM_SIZE as u16;
+    assert_eq!(emu.set_cpu_state(&state), Err(StateError::Invalid));
+
+    assert!(emu.cpu_state() == before);
+}
+
+#[test]
+fn set_pc_and_i_reject_addresses_outside_ram() {
+    let mut emu = Emulator::new();
+    assert_eq!(emu.set_pc(0x300), Ok(()));
+    assert_eq!(emu.set_i(RAM_SIZE as u16 - 1), Ok(()));
+
+    assert_eq!(emu.set_pc(RAM_SIZE as u16), Err(StateError::Invalid));
+    assert_eq!(emu.set_i(0xFFFF), Err(StateError::Invalid));
+    assert_eq!(emu.pc(), 0x300);
+    assert_eq!(emu.i(), RAM_SIZE as u16 - 1);
+}
+
+#[test]
+fn ram_version_moves_with_the_ram() {
+    let mut emu = Emulator::new();
+    let version = emu.ram_version();
+    emu.write_memory(0x300, &[0]);
+    assert_eq!(emu.ram_version(), version, "storing the same byte changes nothing");
+
+    emu.write_memory(0x300, &[1]);
+    let written = emu.ram_version();
+    assert_ne!(written, version);
+
+    emu.load(&[0xF0, 0x55]); // STRR V0
+    emu.set_i(0x300).unwrap();
+    emu.tick();
+    assert_ne!(emu.ram_version(), written);
+}
@@ -0,0 +1,59 @@
+use core::*;
+
+#[test]
+fn every_opcode_round_trips() {
+    let mut valid = 0;
+    for op in 0..=u16::MAX {
+        match decode(op) {
+            Ok(instruction) => {
+                assert_eq!(encode(instruction), op, "{:04X} is {:?}", op, instruction);
+                valid += 1;
+            }
+            Err(error) => assert_eq!(error, DecodeError(op)),
+        }
+    }
+    assert_eq!(valid, 43955);
+}
+
+#[test]
+fn operands_are_split_out() {
+    assert_eq!(decode(0x00E0), Ok(Instruction::Clear));
+    assert_eq!(decode(0x1234), Ok(Instruction::Jmp(0x234)));
+    assert_eq!(decode(0x3A7F), Ok(Instruction::Seq(0xA, 0x7F)));
+    assert_eq!(decode(0x8126), Ok(Instruction::Shr(1, 2)));
+    assert_eq!(decode(0xD12F), Ok(Instruction::Draw(1, 2, 0xF)));
+    assert_eq!(decode(0xF565), Ok(Instruction::Ldr(5)));
+    assert_eq!(decode(0x0123), Err(DecodeError(0x0123)));
+    assert_eq!(decode(0x5121), Err(DecodeError(0x5121)));
+}
+
+#[test]
+fn encode_truncates_operands() {
+    assert_eq!(encode(Instruction::Jmp(0xF234)), 0x1234);
+    assert_eq!(encode(Instruction::Add(0x13, 0x24)), 0x8344);
+}
+
+#[test]
+fn instructions_print_as_assembly() {
+    assert_eq!(Instruction::Seq(3, 0x12).to_string(), "SEQ V3, 0x12");
+    assert_eq!(Instruction::Call(0x2A4).to_string(), "CALL 0x2A4");
+    assert_eq!(Instruction::Draw(0, 1, 5).to_string(), "DRAW V0, V1, 5");
+    assert_eq!(Instruction::Shl(4, 0).to_string(), "SHL V4");
+}
+
+#[test]
+fn unknown_opcodes_stop_the_emulator() {
+    let mut emu = Emulator::new();
+    emu.load(&[0x01, 0x23]);
+    for _ in 0..2 {
+        emu.tick();
+        assert_eq!(emu.pc(), 0x200);
+        assert_eq!(emu.fault(), Some(DecodeError(0x0123)));
+    }
+
+    // Replacing the opcode lets it go on
+    emu.load(&[0x00, 0xE0]); // CLEAR
+    emu.tick();
+    assert_eq!(emu.pc(), 0x202);
+    assert_eq!(emu.fault(), None);
+}
@@ -0,0 +1,80 @@
+use core::profile::Profiler;
+use core::*;
+
+// main calls sub_206 twice, which calls sub_20C each time, then spins
+#[rustfmt::skip]
+const NESTED_CALLS: [u8; 16] = [
+    0x22, 0x06, // 200: CALL 0x206
+    0x22, 0x06, // 202: CALL 0x206
+    0x12, 0x04, // 204: JMP 0x204
+    0x60, 0x01, // 206: LD V0, 0x01
+    0x22, 0x0C, // 208: CALL 0x20C
+    0x00, 0xEE, // 20A: RET
+    0x60, 0x02, // 20C: LD V0, 0x02
+    0x00, 0xEE, // 20E: RET
+];
+
+fn profile(rom: &[u8], ticks: usize) -> Profiler {
+    let mut emu = Emulator::new();
+    emu.load(rom);
+    let mut profiler = Profiler::new();
+    for _ in 0..ticks {
+        profiler.record(&emu);
+        emu.tick();
+    }
+    profiler
+}
+
+#[test]
+fn counts_each_instruction() {
+    // Both calls take 5 instructions each, leaving 3 ticks of the final loop
+    let profiler = profile(&NESTED_CALLS, 15);
+    assert_eq!(profiler.total(), 15);
+    assert_eq!(profiler.count(0x200), 1);
+    assert_eq!(profiler.count(0x204), 3);
+    assert_eq!(profiler.count(0x206), 2);
+    assert_eq!(profiler.count(0x20E), 2);
+    assert_eq!(profiler.count(0x210), 0);
+}
+
+#[test]
+fn charges_nested_subroutines() {
+    let profiler = profile(&NESTED_CALLS, 15);
+    let subroutines: Vec<_> = profiler.subroutines().into_iter().collect();
+    // (entry, (self, with callees))
+    assert_eq!(
+        subroutines,
+        [(0x200, (5, 15)), (0x206, (6, 10)), (0x20C, (4, 4))]
+    );
+}
+
+#[test]
+fn folds_call_stacks() {
+    let profiler = profile(&NESTED_CALLS, 15);
+    assert_eq!(
+        profiler.folded(),
+        "main 5\nmain;sub_206 6\nmain;sub_206;sub_20C 4\n"
+    );
+}
+
+#[test]
+fn recursion_is_charged_once_per_stack() {
+    // sub_204 calls itself until V0 reaches 2
+    #[rustfmt::skip]
+    let rom = [
+        0x22, 0x04, // 200: CALL 0x204
+        0x12, 0x02, // 202: JMP 0x202
+        0x70, 0x01, // 204: ADDIW V0, 0x01
+        0x30, 0x02, // 206: SEQ V0, 0x02
+        0x22, 0x04, // 208: CALL 0x204
+        0x00, 0xEE, // 20A: RET
+    ];
+    // main: CALL; outer: ADDIW, SEQ, CALL; inner: ADDIW, SEQ, RET; outer: RET; main: JMP
+    let profiler = profile(&rom, 9);
+    assert_eq!(
+        profiler.folded(),
+        "main 2\nmain;sub_204 4\nmain;sub_204;sub_204 3\n"
+    );
+    let subroutines: Vec<_> = profiler.subroutines().into_iter().collect();
+    assert_eq!(subroutines, [(0x200, (2, 9)), (0x204, (7, 7))]);
+}
@@ -0,0 +1,165 @@
+//! Lockstep tests of the recompiler against the interpreter: both run the same program, and their
+//! whole state is compared after every batch of instructions.
+
+use core::recompiler::Recompiler;
+use core::*;
+
+use std::panic::{self, AssertUnwindSafe};
+
+// Rewrites the operand of its own `LD V1` every iteration, and stores BCD digits next to it
+#[rustfmt::skip]
+const SELF_MODIFYING: [u8; 33] = [
+    0xA2, 0x0E, // 200: LDI 0x20E
+    0x60, 0x61, // 202: LD V0, 0x61
+    0x72, 0x01, // 204: ADDIW V2, 0x01
+    0x81, 0x20, // 206: MV V1, V2
+    0x60, 0x61, // 208: LD V0, 0x61
+    0xF1, 0x55, // 20A: STRR V1
+    0xA2, 0x0E, // 20C: LDI 0x20E
+    0x61, 0x00, // 20E: LD V1, 0x00, patched by the STRR
+    0x83, 0x14, // 210: ADD V3, V1
+    0xC4, 0xFF, // 212: RND V4, 0xFF
+    0xA2, 0x1E, // 214: LDI 0x21E
+    0xF3, 0x33, // 216: SBCD V3
+    0xF2, 0x65, // 218: LDR V2
+    0xA2, 0x0E, // 21A: LDI 0x20E
+    0x12, 0x04, // 21C: JMP 0x204
+    0x00, 0x00, 0x00, // 21E: BCD digits
+];
+
+// Stores the bytes of its `LD V2` over themselves every iteration
+#[rustfmt::skip]
+const REWRITES_ITSELF: [u8; 12] = [
+    0xA2, 0x06, // 200: LDI 0x206
+    0x60, 0x62, // 202: LD V0, 0x62
+    0x61, 0x05, // 204: LD V1, 0x05
+    0x62, 0x05, // 206: LD V2, 0x05, rewritten by the STRR
+    0xF1, 0x55, // 208: STRR V1
+    0x12, 0x00, // 20A: JMP 0x200
+];
+
+fn state(emu: &Emulator) -> Vec<u8> {
+    let mut buffer = vec![0; STATE_SIZE];
+    emu.save_state(&mut buffer).unwrap();
+    buffer
+}
+
+struct Xorshift(u32);
+
+impl Xorshift {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+// A program of valid opcodes, jumping and pointing I into itself most of the time
+fn random_program(rng: &mut Xorshift) -> Vec<u8> {
+    let mut program = Vec::new();
+    while program.len() < 256 {
+        let mut op = rng.next() as u16;
+        if matches!(op >> 12, 1 | 2 | 0xA | 0xB) {
+            op = (op & 0xF000) | (0x200 + (op & 0x1FE));
+        }
+        if op >> 12 == 0 {
+            op = [0x0000, 0x00E0, 0x00EE][rng.next() as usize % 3];
+        }
+        if decode(op).is_ok() {
+            program.extend(op.to_be_bytes());
+        }
+    }
+    program
+}
+
+// Runs the program on the interpreter and through the recompiler, `chunk` instructions at a
+// time, with key presses and a rewrite of the program's first bytes between frames. A panic,
+// e.g. from a stack overflow, has to happen on both sides, and ends the run.
+fn lockstep(program: &[u8], quirks: Quirks, seed: u32, chunk: usize, frames: usize) -> Recompiler {
+    let mut interpreted = Emulator::new();
+    let mut recompiled = Emulator::new();
+    let mut recompiler = Recompiler::new();
+    for emu in [&mut interpreted, &mut recompiled] {
+        emu.set_quirks(quirks);
+        emu.seed(seed);
+        emu.load(program);
+    }
+
+    for frame in 0..frames {
+        let left = panic::catch_unwind(AssertUnwindSafe(|| {
+            for _ in 0..chunk {
+                interpreted.tick();
+            }
+        }));
+        let right = panic::catch_unwind(AssertUnwindSafe(|| {
+            recompiler.run(&mut recompiled, chunk);
+        }));
+        assert_eq!(left.is_err(), right.is_err(), "only one side panicked");
+        if left.is_err() {
+            return recompiler;
+        }
+        assert!(
+            state(&interpreted) == state(&recompiled),
+            "diverged in frame {}",
+            frame
+        );
+
+        let key = frame * 7 % NUM_KEYS;
+        for emu in [&mut interpreted, &mut recompiled] {
+            emu.tick_timers();
+            emu.keypress(key, frame % 3 == 0);
+            if frame % 50 == 49 {
+                emu.load(&program[..4]);
+            }
+        }
+    }
+    recompiler
+}
+
+#[test]
+fn random_programs_run_in_lockstep() {
+    // Most random programs end up panicking, e.g. on a stack overflow, which is tested too
+    panic::set_hook(Box::new(|_| {}));
+    let mut rng = Xorshift(12345);
+    for n in 0..400 {
+        let program = random_program(&mut rng);
+        let quirks = Quirks {
+            display_wait: n % 2 == 0,
+            key_release: n % 3 == 0,
+        };
+        let chunk = [1, 7, 10, 100][n % 4];
+        lockstep(&program, quirks, n as u32, chunk, 200);
+    }
+    let _ = panic::take_hook();
+}
+
+#[test]
+fn self_modifying_code_runs_in_lockstep() {
+    for chunk in [1, 3, 10, 1000] {
+        lockstep(&SELF_MODIFYING, Quirks::default(), 7, chunk, 300);
+    }
+}
+
+#[test]
+fn code_rewritten_with_its_own_bytes_stays_compiled() {
+    let recompiler = lockstep(&REWRITES_ITSELF, Quirks::default(), 1, 10, 300);
+    assert!(recompiler.is_compiled(0x200));
+}
+
+#[test]
+fn reused_recompiler_runs_each_emulators_program() {
+    let mut first = Emulator::new();
+    let mut second = Emulator::new();
+    first.load(&[0x60, 0x11, 0x12, 0x00]); // LD V0, 0x11; JMP 0x200
+    second.load(&[0x60, 0x22, 0x12, 0x00]); // LD V0, 0x22; JMP 0x200
+
+    let mut recompiler = Recompiler::new();
+    recompiler.run(&mut first, 4);
+    recompiler.run(&mut second, 4);
+    assert_eq!(first.registers()[0], 0x11);
+    assert_eq!(second.registers()[0], 0x22);
+
+    recompiler.run(&mut first, 4);
+    assert_eq!(first.registers()[0], 0x11);
+}
@@ -0,0 +1,113 @@
+use core::selfmod::{Detector, Event, Kind};
+use core::*;
+
+fn events(rom: &[u8], ticks: usize) -> Vec<Event> {
+    let mut emu = Emulator::new();
+    emu.load(rom);
+    let mut detector = Detector::new();
+    let mut events = Vec::new();
+    for _ in 0..ticks {
+        events.extend(detector.record(&emu));
+        emu.tick();
+    }
+    events
+}
+
+#[test]
+fn reports_stores_over_executed_code() {
+    #[rustfmt::skip]
+    let rom = [
+        0x60, 0x12, // 200: LD V0, 0x12
+        0xA2, 0x00, // 202: LDI 0x200
+        0xF0, 0x55, // 204: STRR V0, over the LD
+        0x12, 0x00, // 206: JMP 0x200
+    ];
+    // Then 200 runs as JMP 0x212, made of the byte just stored
+    let events = events(&rom, 20);
+    assert_eq!(
+        events,
+        [
+            Event {
+                kind: Kind::CodeWritten,
+                pc: 0x204,
+                address: 0x200,
+                opcode: 0xF055,
+            },
+            Event {
+                kind: Kind::DataExecuted,
+                pc: 0x200,
+                address: 0x200,
+                opcode: 0x1212,
+            }
+        ]
+    );
+    assert_eq!(
+        events[0].to_string(),
+        "204: STRR V0 wrote to 200, which ran as code"
+    );
+}
+
+#[test]
+fn reports_each_event_once() {
+    #[rustfmt::skip]
+    let rom = [
+        0xA2, 0x09, // 200: LDI 0x209
+        0x70, 0x01, // 202: ADDIW V0, 0x01
+        0xF0, 0x55, // 204: STRR V0, over the operand of the LD below
+        0x12, 0x08, // 206: JMP 0x208
+        0x61, 0x00, // 208: LD V1, patched
+        0x12, 0x02, // 20A: JMP 0x202
+    ];
+    let events = events(&rom, 60);
+    let kinds: Vec<_> = events
+        .iter()
+        .map(|event| (event.kind, event.pc, event.address))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (Kind::DataExecuted, 0x208, 0x209),
+            (Kind::CodeWritten, 0x204, 0x209)
+        ]
+    );
+}
+
+#[test]
+fn reports_jumps_into_stored_bytes() {
+    #[rustfmt::skip]
+    let rom = [
+        0x60, 0x12, // 200: LD V0, 0x12
+        0x61, 0x0A, // 202: LD V1, 0x0A
+        0xA2, 0x0A, // 204: LDI 0x20A
+        0xF1, 0x55, // 206: STRR V1, storing JMP 0x20A at 20A
+        0x12, 0x0A, // 208: JMP 0x20A
+        0x00, 0x00, // 20A: NOP, until overwritten
+    ];
+    let events = events(&rom, 10);
+    assert_eq!(
+        events,
+        [Event {
+            kind: Kind::DataExecuted,
+            pc: 0x20A,
+            address: 0x20A,
+            opcode: 0x120A,
+        }]
+    );
+    assert_eq!(
+        events[0].to_string(),
+        "20A: JMP 0x20A ran from bytes written to 20A"
+    );
+}
+
+#[test]
+fn loaded_bytes_are_not_data() {
+    // Reads its own code as a sprite, and runs bytes only ever loaded
+    #[rustfmt::skip]
+    let rom = [
+        0xA2, 0x00, // 200: LDI 0x200
+        0xD0, 0x05, // 202: DRAW V0, V0, 5
+        0x12, 0x06, // 204: JMP 0x206
+        0x12, 0x00, // 206: JMP 0x200
+    ];
+    assert!(events(&rom, 50).is_empty());
+}
@@ -0,0 +1,43 @@
+use core::*;
+
+// RND V0, 0xFF; STRR V0 to I, then I += 1, forever
+const RANDOM_BYTES: [u8; 10] = [0xA3, 0x00, 0xC0, 0xFF, 0xF0, 0x55, 0xF1, 0x1E, 0x12, 0x02];
+
+fn run(emu: &mut Emulator, ticks: usize) -> Vec<u8> {
+    for _ in 0..ticks {
+        emu.tick();
+    }
+    let mut state = vec![0; STATE_SIZE];
+    emu.save_state(&mut state).unwrap();
+    state
+}
+
+#[test]
+fn seeded_runs_replay_in_a_fresh_emulator() {
+    let mut emu = Emulator::new();
+    emu.seed(42);
+    emu.load(&RANDOM_BYTES);
+    let saved = run(&mut emu, 100);
+    let played = run(&mut emu, 400);
+
+    let mut fresh = Emulator::new();
+    fresh.load_state(&saved).unwrap();
+    assert!(run(&mut fresh, 400) == played, "the replay diverged");
+}
+
+#[test]
+fn rejects_other_data() {
+    let mut emu = Emulator::new();
+    let mut state = vec![0; STATE_SIZE];
+    assert_eq!(
+        emu.save_state(&mut state[..STATE_SIZE - 1]),
+        Err(StateError::BufferTooSmall)
+    );
+    emu.save_state(&mut state).unwrap();
+    assert_eq!(
+        emu.load_state(&state[..STATE_SIZE - 1]),
+        Err(StateError::BufferTooSmall)
+    );
+    state[0] = b'X';
+    assert_eq!(emu.load_state(&state), Err(StateError::Invalid));
+}
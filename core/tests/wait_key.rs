@@ -0,0 +1,54 @@
+use core::*;
+
+// WKP V0 at 0x200, then spins at 0x202
+const WAIT: [u8; 4] = [0xF0, 0x0A, 0x12, 0x02];
+
+fn waiting_emulator() -> Emulator {
+    let mut emu = Emulator::new();
+    emu.set_quirks(Quirks {
+        display_wait: false,
+        key_release: true,
+    });
+    emu.load(&WAIT);
+    emu
+}
+
+#[test]
+fn key_release_waits_for_a_press_and_release() {
+    let mut emu = waiting_emulator();
+    emu.tick();
+    emu.keypress(5, true);
+    emu.tick();
+    assert_eq!(emu.pc(), 0x200);
+    emu.keypress(5, false);
+    emu.tick();
+    assert_eq!(emu.pc(), 0x202);
+    assert_eq!(emu.registers()[0], 5);
+}
+
+#[test]
+fn key_release_ignores_keys_held_before_the_wait() {
+    let mut emu = waiting_emulator();
+    emu.keypress(3, true);
+    emu.tick();
+    emu.keypress(3, false);
+    emu.tick();
+    assert_eq!(emu.pc(), 0x200, "released a key pressed before FX0A");
+
+    emu.keypress(3, true);
+    emu.tick();
+    emu.keypress(3, false);
+    emu.tick();
+    assert_eq!(emu.pc(), 0x202);
+    assert_eq!(emu.registers()[0], 3);
+}
+
+#[test]
+fn key_release_catches_taps_between_ticks() {
+    let mut emu = waiting_emulator();
+    emu.tick();
+    emu.keypress(0xA, true);
+    emu.keypress(0xA, false);
+    emu.tick();
+    assert_eq!(emu.registers()[0], 0xA);
+}
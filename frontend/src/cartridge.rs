@@ -0,0 +1,202 @@
+use core::Quirks;
+
+use serde::Deserialize;
+
+use crate::palette::Palette;
+
+/// A game shared as an Octo cartridge: a GIF whose label hides the program and its options.
+pub struct Cartridge {
+    /// Octo assembly source of the program.
+    pub program: String,
+    pub quirks: Quirks,
+    pub ticks_per_frame: Option<usize>,
+    pub palette: Option<Palette>,
+    /// Options the cartridge sets that chip-r can't honour, so the game may misbehave.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    program: String,
+    #[serde(default)]
+    options: Settings,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Settings {
+    tickrate: Option<usize>,
+    background_color: Option<String>,
+    fill_color: Option<String>,
+    fill_color2: Option<String>,
+    blend_color: Option<String>,
+    #[serde(default)]
+    v_blank_quirks: bool,
+    #[serde(default)]
+    shift_quirks: bool,
+    #[serde(default)]
+    load_store_quirks: bool,
+    #[serde(default)]
+    jump_quirks: bool,
+    #[serde(default)]
+    clip_quirks: bool,
+}
+
+pub fn is_cartridge(path: &str) -> bool {
+    path.to_lowercase().ends_with(".gif")
+}
+
+/// Octo spreads the payload over the low nybble of every pixel's palette index, two pixels per
+/// byte and frame after frame, so the label drawn with the high nybbles stays visible. The
+/// payload is a 32-bit big endian length followed by that many bytes of JSON, holding the
+/// program's source and its options.
+pub fn load(gif: &[u8]) -> Result<Cartridge, String> {
+    let invalid = |error: &dyn std::fmt::Display| format!("Invalid Octo cartridge: {}", error);
+
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(gif).map_err(|e| invalid(&e))?;
+
+    let mut nybbles = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| invalid(&e))? {
+        nybbles.extend(frame.buffer.iter().map(|index| index & 0xF));
+    }
+    let bytes: Vec<u8> = nybbles
+        .chunks_exact(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect();
+
+    let (length, data) = bytes
+        .split_first_chunk::<4>()
+        .ok_or_else(|| invalid(&"missing payload"))?;
+    let json = data
+        .get(..u32::from_be_bytes(*length) as usize)
+        .ok_or_else(|| invalid(&"truncated payload"))?;
+    let payload: Payload = serde_json::from_slice(json).map_err(|e| invalid(&e))?;
+
+    let settings = payload.options;
+    // Octo lists the colours in the same order as our palettes, but only the first two are required
+    let colors: Vec<&str> = [
+        &settings.background_color,
+        &settings.fill_color,
+        &settings.fill_color2,
+        &settings.blend_color,
+    ]
+    .into_iter()
+    .map_while(|color| color.as_deref())
+    .collect();
+
+    // Octo quirks chip-r has no switch for, and the setting matching how it always behaves:
+    // shifting VX in place, leaving I alone in FX55 and FX65, jumping by V0 and wrapping sprites
+    let fixed = [
+        ("shiftQuirks", settings.shift_quirks, true),
+        ("loadStoreQuirks", settings.load_store_quirks, true),
+        ("jumpQuirks", settings.jump_quirks, false),
+        ("clipQuirks", settings.clip_quirks, false),
+    ];
+    let warnings = fixed
+        .into_iter()
+        .filter(|&(_, wanted, supported)| wanted != supported)
+        .map(|(name, wanted, _)| format!("Unsupported quirk: {} is {}.", name, wanted))
+        .collect();
+
+    Ok(Cartridge {
+        program: payload.program,
+        quirks: Quirks {
+            display_wait: settings.v_blank_quirks,
+            // Octo always resumes FX0A when the key is released
+            key_release: true,
+        },
+        ticks_per_frame: settings.tickrate,
+        palette: Palette::from_hex_list(&colors.join(",")).ok(),
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sdl2::pixels::Color;
+
+    // Packs the payload the way Octo does, into the low nybbles of a one pixel high GIF
+    fn encode(payload: &[u8]) -> Vec<u8> {
+        let pixels: Vec<u8> = payload
+            .iter()
+            .flat_map(|byte| [0x10 | (byte >> 4), 0x10 | (byte & 0xF)])
+            .collect();
+
+        let palette: Vec<u8> = (0..=255).flat_map(|index| [index, index, index]).collect();
+        let mut gif = Vec::new();
+        let mut encoder = gif::Encoder::new(&mut gif, pixels.len() as u16, 1, &palette).unwrap();
+        let frame = gif::Frame::from_indexed_pixels(pixels.len() as u16, 1, pixels, None);
+        encoder.write_frame(&frame).unwrap();
+        drop(encoder);
+        gif
+    }
+
+    fn cartridge(json: &str) -> Vec<u8> {
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend_from_slice(json.as_bytes());
+        encode(&payload)
+    }
+
+    #[test]
+    fn loads_program_and_options() {
+        let gif = cartridge(
+            r##"{"program": ": main\n  loop again",
+                "options": {"tickrate": 20, "vBlankQuirks": true,
+                            "shiftQuirks": true, "loadStoreQuirks": true,
+                            "backgroundColor": "#000000", "fillColor": "#FF0000"}}"##,
+        );
+        let cartridge = load(&gif).unwrap();
+
+        assert_eq!(cartridge.program, ": main\n  loop again");
+        assert_eq!(cartridge.ticks_per_frame, Some(20));
+        assert_eq!(
+            cartridge.quirks,
+            Quirks {
+                display_wait: true,
+                key_release: true,
+            }
+        );
+        let palette = cartridge.palette.unwrap();
+        assert_eq!(palette.background(), Color::RGB(0, 0, 0));
+        assert_eq!(palette.foreground(), Color::RGB(0xFF, 0, 0));
+        assert!(cartridge.warnings.is_empty());
+    }
+
+    #[test]
+    fn warns_about_unsupported_quirks() {
+        let gif =
+            cartridge(r#"{"program": "", "options": {"jumpQuirks": true, "clipQuirks": true}}"#);
+        let cartridge = load(&gif).unwrap();
+
+        assert_eq!(
+            cartridge.warnings,
+            [
+                "Unsupported quirk: shiftQuirks is false.",
+                "Unsupported quirk: loadStoreQuirks is false.",
+                "Unsupported quirk: jumpQuirks is true.",
+                "Unsupported quirk: clipQuirks is true.",
+            ]
+        );
+        assert!(cartridge.palette.is_none());
+        assert_eq!(cartridge.ticks_per_frame, None);
+    }
+
+    #[test]
+    fn rejects_broken_payloads() {
+        let error = |gif: &[u8]| load(gif).err().unwrap();
+        assert_eq!(
+            error(&encode(&[0, 0])),
+            "Invalid Octo cartridge: missing payload"
+        );
+        assert_eq!(
+            error(&encode(&[0, 0, 0, 3, b'{', b'}'])),
+            "Invalid Octo cartridge: truncated payload"
+        );
+        assert!(error(&cartridge("{}")).starts_with("Invalid Octo cartridge: missing field"));
+        assert!(error(b"GIF89a").starts_with("Invalid Octo cartridge: "));
+    }
+}
@@ -0,0 +1,176 @@
+use core::cheat::{Freezer, RamSearch};
+use core::{Emulator, RAM_SIZE};
+
+use std::fs;
+use std::path::Path;
+
+/// A named set of RAM bytes to freeze.
+pub struct Cheat {
+    pub name: String,
+    pub codes: Vec<(usize, u8)>,
+}
+
+/// Loads the cheats next to a ROM, in a file with the same name plus `.cht`, e.g. `pong.ch8.cht`.
+/// Having no cheat file is fine, a broken one is not.
+pub fn load_for_rom(rom: &str) -> Result<Vec<Cheat>, String> {
+    let path = format!("{}.cht", rom);
+    if !Path::new(&path).exists() {
+        return Ok(Vec::new());
+    }
+    load(&path)
+}
+
+/// Cheat files have one cheat per line, as a name and the `address:value` codes it freezes,
+/// both in hex. Lines starting with `#` are comments.
+///
+/// ```text
+/// # Pong
+/// Infinite lives = 0x2F4:03
+/// Max score = 3F0:09, 3F1:09
+/// ```
+pub fn load(path: &str) -> Result<Vec<Cheat>, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|error| format!("Unable to read {}: {}", path, error))?;
+
+    let mut cheats = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let cheat = parse_line(line)
+            .ok_or_else(|| format!("Invalid cheat at {}:{}: \"{}\"", path, number + 1, line))?;
+        cheats.push(cheat);
+    }
+    Ok(cheats)
+}
+
+pub fn freezer(cheats: &[Cheat]) -> Freezer {
+    let mut freezer = Freezer::new();
+    for (address, value) in cheats.iter().flat_map(|cheat| &cheat.codes) {
+        freezer.freeze(*address, *value);
+    }
+    freezer
+}
+
+// Candidates listed at most by a search report, more aren't worth reading through
+const MAX_LISTED: usize = 16;
+
+/// Describes where a RAM search stands: how many addresses are left and, when there are few,
+/// their values in the cheat file's `address:value` format.
+pub fn search_report(search: &RamSearch, emu: &Emulator) -> String {
+    let mut report = format!("RAM search: {} candidates\n", search.count());
+    if search.count() <= MAX_LISTED {
+        for address in search.candidates() {
+            let value = emu.memory(address..address + 1)[0];
+            report += &format!("  {:03X}:{:02X}\n", address, value);
+        }
+    }
+    report
+}
+
+fn parse_line(line: &str) -> Option<Cheat> {
+    let (name, codes) = line.rsplit_once('=')?;
+    let codes = codes
+        .split(',')
+        .map(|code| {
+            let (address, value) = code.trim().split_once(':')?;
+            let address = parse_hex(address).filter(|&address| address < RAM_SIZE)?;
+            let value = u8::try_from(parse_hex(value)?).ok()?;
+            Some((address, value))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Cheat {
+        name: name.trim().to_string(),
+        codes,
+    })
+}
+
+fn parse_hex(number: &str) -> Option<usize> {
+    let digits = number.trim().trim_start_matches("0x");
+    usize::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    // Writes a cheat file named after the test, so tests running in parallel don't share one
+    fn cheat_file(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("chip-r-{}-{}.cht", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn loads_cheats_and_skips_comments() {
+        let path = cheat_file(
+            "valid",
+            "# Pong\n\nLives = 0x2F4:03\nScore = 3F0:09, 3F1:9\n",
+        );
+        let cheats = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(cheats.len(), 2);
+        assert_eq!(cheats[0].name, "Lives");
+        assert_eq!(cheats[0].codes, [(0x2F4, 3)]);
+        assert_eq!(cheats[1].name, "Score");
+        assert_eq!(cheats[1].codes, [(0x3F0, 9), (0x3F1, 9)]);
+    }
+
+    #[test]
+    fn rejects_addresses_outside_ram() {
+        let path = cheat_file("address", "Lives = 2F4:03\nBroken = 1000:01\n");
+        let error = load(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            error,
+            format!("Invalid cheat at {}:2: \"Broken = 1000:01\"", path)
+        );
+    }
+
+    #[test]
+    fn rejects_values_that_dont_fit_a_byte() {
+        for (name, line) in [
+            ("large", "Lives = 2F4:100"),
+            ("hex", "Lives = 2F4:zz"),
+            ("missing", "Lives = 2F4"),
+        ] {
+            let path = cheat_file(name, line);
+            let error = load(&path).err().unwrap();
+            fs::remove_file(&path).unwrap();
+            assert_eq!(error, format!("Invalid cheat at {}:1: \"{}\"", path, line));
+        }
+    }
+
+    #[test]
+    fn missing_files_are_only_fine_next_to_the_rom() {
+        let rom = env::temp_dir().join("chip-r-no-such-rom.ch8");
+        let rom = rom.to_string_lossy();
+        assert!(load_for_rom(&rom).unwrap().is_empty());
+
+        let error = load(&format!("{}.cht", rom)).err().unwrap();
+        assert!(error.starts_with("Unable to read "), "{}", error);
+    }
+
+    #[test]
+    fn search_report_lists_few_candidates() {
+        let mut emu = Emulator::new();
+        let mut search = RamSearch::new(&emu);
+        assert_eq!(
+            search_report(&search, &emu),
+            "RAM search: 4096 candidates\n"
+        );
+
+        emu.write_memory(0x2F4, &[3]);
+        search.filter(&emu, core::cheat::Comparison::Changed);
+        assert_eq!(
+            search_report(&search, &emu),
+            "RAM search: 1 candidates\n  2F4:03\n"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use core::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// How much of a pixel's brightness survives each frame after it's turned off
+const DECAY_RATE: f32 = 0.6;
+// Below this a pixel is considered dark, so it doesn't linger forever as an invisible tint
+const DECAY_CUTOFF: f32 = 0.05;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Filter {
+    None,
+    /// Pixels fade out over a few frames, like the phosphor of a CRT.
+    Decay,
+    /// Each frame is averaged with the previous one.
+    Blend,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 3] = [Filter::None, Filter::Decay, Filter::Blend];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::None => "none",
+            Filter::Decay => "decay",
+            Filter::Blend => "blend",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Filter> {
+        Filter::ALL
+            .iter()
+            .find(|filter| filter.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    pub fn next(&self) -> Filter {
+        let i = Filter::ALL
+            .iter()
+            .position(|filter| filter == self)
+            .unwrap();
+        Filter::ALL[(i + 1) % Filter::ALL.len()]
+    }
+}
+
+/// Turns the emulator's on/off pixels into per pixel intensities between 0.0 and 1.0, hiding the
+/// flicker caused by games that erase and redraw their sprites every frame.
+pub struct Phosphor {
+    pub filter: Filter,
+    intensity: [f32; SCREEN_WIDTH * SCREEN_HEIGHT],
+    previous: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    fading: bool,
+}
+
+impl Phosphor {
+    pub fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            intensity: [0.0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            previous: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            fading: false,
+        }
+    }
+
+    /// Whether the last frame changed any intensity. If so, the following frames may keep
+    /// changing even when the display doesn't, e.g. while pixels fade out.
+    pub fn is_fading(&self) -> bool {
+        self.fading
+    }
+
+    /// Feeds a new frame through the filter, returning the intensities to display.
+    pub fn apply(&mut self, display: &[bool]) -> &[f32] {
+        self.fading = false;
+        for (i, &lit) in display.iter().enumerate() {
+            let intensity = match self.filter {
+                Filter::None => on_off(lit),
+                Filter::Decay if lit => 1.0,
+                Filter::Decay => {
+                    let faded = self.intensity[i] * DECAY_RATE;
+                    if faded < DECAY_CUTOFF {
+                        0.0
+                    } else {
+                        faded
+                    }
+                }
+                Filter::Blend => (on_off(lit) + on_off(self.previous[i])) / 2.0,
+            };
+            self.fading |= intensity != self.intensity[i];
+            self.intensity[i] = intensity;
+            self.previous[i] = lit;
+        }
+
+        &self.intensity
+    }
+}
+
+fn on_off(lit: bool) -> f32 {
+    if lit {
+        1.0
+    } else {
+        0.0
+    }
+}
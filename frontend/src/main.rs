@@ -1,14 +1,34 @@
+mod cartridge;
+mod cheats;
+mod filter;
+mod options;
+mod overlay;
+mod palette;
+mod romdb;
+mod script;
+mod session;
+
+use core::analysis::Cfg;
+use core::cheat::{Comparison, RamSearch};
+use core::coverage::Coverage;
+use core::gdb::GdbStub;
+use core::profile::Profiler;
+use core::recompiler::Recompiler;
+use core::selfmod::Detector;
 use core::*;
+use filter::Phosphor;
+use options::{Options, USAGE};
+use palette::{Palette, PALETTES};
+use script::Script;
+use session::Session;
 
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::Texture;
 
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
 
 const SCALE: u32 = 15; // 15x native scale
@@ -17,20 +37,153 @@ const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
 const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: cargo run <path-to-game>");
+    let args: Vec<_> = env::args().skip(1).collect();
+    let options = match Options::parse(&args) {
+        Ok(options) => options,
+        Err(error) => {
+            println!("{}\n\n{}", error, USAGE);
+            return;
+        }
+    };
+    let mut rom = File::open(&options.rom).expect("Unable to open the rom requested.");
+    let mut buffer = Vec::new();
+    rom.read_to_end(&mut buffer).expect("Unable to read rom.");
+
+    // Octo cartridges carry their own settings, which take precedence over the ROM database's
+    let cartridge = cartridge::is_cartridge(&options.rom)
+        .then(|| cartridge::load(&buffer).unwrap_or_else(|e| panic!("{}", e)));
+    for warning in cartridge.iter().flat_map(|cartridge| &cartridge.warnings) {
+        println!("{}", warning);
+    }
+
+    // Known games get their recommended settings, unless overridden from the command line. The
+    // database only knows ROMs, so source files are never found in it.
+    let octo_source = options.rom.to_lowercase().ends_with(".8o");
+    let entry = if cartridge.is_some() || octo_source {
+        None
+    } else {
+        romdb::lookup(options.romdb.as_deref(), &buffer).unwrap_or_else(|e| panic!("{}", e))
+    };
+    if let Some(entry) = entry.as_ref() {
+        println!("{} by {}", entry.title, entry.authors.join(", "));
+        if let Some(platform) = entry.platform.as_ref() {
+            println!("Platform: {}", platform);
+        }
+    }
+    let palette = options
+        .palette
+        .or(cartridge.as_ref().and_then(|cartridge| cartridge.palette))
+        .or(entry.as_ref().and_then(|entry| entry.palette))
+        .unwrap_or(PALETTES[0]);
+    let quirks = options
+        .quirks
+        .or(cartridge.as_ref().map(|cartridge| cartridge.quirks))
+        .or(entry.as_ref().map(|entry| entry.quirks))
+        .unwrap_or_default();
+    let ticks_per_frame = options
+        .ticks_per_frame
+        .or(cartridge
+            .as_ref()
+            .and_then(|cartridge| cartridge.ticks_per_frame))
+        .or(entry.as_ref().and_then(|entry| entry.ticks_per_frame))
+        .unwrap_or(TICKS_PER_FRAME);
+    let extra_keys = entry.map(|entry| entry.keys).unwrap_or_default();
+    let phosphor = Phosphor::new(options.filter);
+
+    // Cartridges and .8o files hold Octo source rather than a ROM
+    let source = match cartridge.as_ref() {
+        Some(cartridge) => Some(cartridge.program.clone()),
+        None if octo_source => Some(String::from_utf8_lossy(&buffer).into_owned()),
+        None => None,
+    };
+    if let Some(source) = source {
+        buffer = assembler::assemble(&source)
+            .unwrap_or_else(|error| panic!("Unable to assemble {}: {}", options.rom, error));
+    }
+
+    // Static analysis only needs the ROM, so it runs without opening a window
+    if options.disassemble || options.cfg.is_some() {
+        let cfg = Cfg::new(&buffer);
+        if options.disassemble {
+            print!("{}", cfg.listing(&buffer));
+        }
+        if let Some(path) = options.cfg.as_ref() {
+            fs::write(path, cfg.to_dot(&buffer)).expect("Unable to write the control-flow graph.");
+        }
         return;
     }
 
-    let sdl = sdl2::init().unwrap();
     let mut emu = Emulator::new();
-
-    let mut rom = File::open(&args[1]).expect("Unable to open the rom requested.");
-    let mut buffer = Vec::new();
-    rom.read_to_end(&mut buffer).expect("Unable to read rom.");
+    emu.set_quirks(quirks);
     emu.load(&buffer);
 
+    let cheats = match options.cheats.as_ref() {
+        Some(path) => cheats::load(path),
+        None => cheats::load_for_rom(&options.rom),
+    };
+    let cheats = cheats.unwrap_or_else(|error| panic!("{}", error));
+    for cheat in &cheats {
+        println!("Cheat: {}", cheat.name);
+    }
+
+    let script = options
+        .script
+        .as_ref()
+        .map(|path| match Script::load(path) {
+            Ok(script) => script,
+            Err(error) => panic!("{}", error),
+        });
+
+    let gdb = options.gdb_port.map(|port| {
+        let stub = GdbStub::bind(port).expect("Unable to listen for a debugger.");
+        println!("Waiting for a debugger on localhost:{}", port);
+        stub
+    });
+
+    let mut session = Session {
+        emu,
+        ticks_per_frame,
+        freezer: cheats::freezer(&cheats),
+        cheats_enabled: !cheats.is_empty(),
+        search: None,
+        script,
+        gdb,
+        profiler: (options.profile || options.folded.is_some()).then(Profiler::new),
+        coverage: options.coverage.is_some().then(Coverage::new),
+        selfmod: options.selfmod.then(Detector::new),
+        recompiler: options.recompile.then(Recompiler::new),
+    };
+
+    match options.frames {
+        Some(frames) => {
+            for _ in 0..frames {
+                session.frame();
+            }
+        }
+        None => run_window(&mut session, palette, phosphor, &extra_keys),
+    }
+
+    if let Some(profiler) = session.profiler.as_ref() {
+        if options.profile {
+            print!("{}", profiler.report());
+        }
+        if let Some(path) = options.folded.as_ref() {
+            fs::write(path, profiler.folded()).expect("Unable to write the profile.");
+        }
+    }
+    if let (Some(coverage), Some(path)) = (session.coverage.as_ref(), options.coverage.as_ref()) {
+        fs::write(path, coverage.annotated_listing(&buffer))
+            .expect("Unable to write the coverage.");
+    }
+}
+
+fn run_window(
+    session: &mut Session,
+    mut palette: Palette,
+    mut phosphor: Phosphor,
+    extra_keys: &[(Keycode, usize)],
+) {
+    let sdl = sdl2::init().unwrap();
     let video_subsystem = sdl.video().unwrap();
     let window = video_subsystem
         .window("chip-r", WINDOW_WIDTH, WINDOW_HEIGHT)
@@ -43,6 +196,17 @@ fn main() {
     canvas.clear();
     canvas.present();
 
+    // The screen is drawn at native resolution and scaled up by SDL when copied to the window
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+        )
+        .unwrap();
+    let mut repaint = true;
+
     let mut event_pump = sdl.event_pump().unwrap();
     'gameloop: loop {
         for event in event_pump.poll_iter() {
@@ -54,56 +218,122 @@ fn main() {
                 } => {
                     break 'gameloop;
                 }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F1),
+                    ..
+                } => {
+                    palette = next_palette(&palette);
+                    println!("Palette: {}", palette.name);
+                    repaint = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    ..
+                } => {
+                    phosphor.filter = phosphor.filter.next();
+                    println!("Filter: {}", phosphor.filter.name());
+                    repaint = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    ..
+                } => {
+                    session.cheats_enabled = !session.cheats_enabled;
+                    let state = if session.cheats_enabled { "on" } else { "off" };
+                    println!("Cheats: {}", state);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F4),
+                    ..
+                } => {
+                    let search = RamSearch::new(&session.emu);
+                    print!("{}", cheats::search_report(&search, &session.emu));
+                    session.search = Some(search);
+                }
+                Event::KeyDown {
+                    keycode: Some(key @ (Keycode::F5 | Keycode::F6 | Keycode::F7 | Keycode::F8)),
+                    ..
+                } => {
+                    if let Some(search) = session.search.as_mut() {
+                        let comparison = match key {
+                            Keycode::F5 => Comparison::Changed,
+                            Keycode::F6 => Comparison::Unchanged,
+                            Keycode::F7 => Comparison::Increased,
+                            _ => Comparison::Decreased,
+                        };
+                        search.filter(&session.emu, comparison);
+                        print!("{}", cheats::search_report(search, &session.emu));
+                    }
+                }
                 Event::KeyDown {
                     keycode: Some(key), ..
                 } => {
-                    let command = key_to_button(key);
-                    if command.is_some() {
-                        emu.keypress(command.unwrap(), true);
+                    if let Some(command) = key_to_button(key, extra_keys) {
+                        session.emu.keypress(command, true);
                     }
                 }
                 Event::KeyUp {
                     keycode: Some(key), ..
                 } => {
-                    let command = key_to_button(key);
-                    if command.is_some() {
-                        emu.keypress(command.unwrap(), false);
+                    if let Some(command) = key_to_button(key, extra_keys) {
+                        session.emu.keypress(command, false);
                     }
                 }
                 _ => (),
             }
         }
 
-        // Refresh rate of drawing
-        for _ in 0..TICKS_PER_FRAME {
-            emu.tick();
+        session.frame();
+
+        // Unchanged frames skip the texture upload, but are still presented to keep vsync pacing
+        if session.emu.display_changed() || phosphor.is_fading() || repaint {
+            draw_screen(&session.emu, &mut texture, &palette, &mut phosphor);
+            repaint = false;
         }
-        emu.tick_timers();
-        draw_screen(&emu, &mut canvas);
+        canvas.copy(&texture, None, None).unwrap();
+        if let Some(script) = session.script.as_ref() {
+            overlay::draw(&mut canvas, &script.overlay());
+        }
+        canvas.present();
     }
 }
 
-fn draw_screen(emu: &Emulator, canvas: &mut Canvas<Window>) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
+fn draw_screen(emu: &Emulator, texture: &mut Texture, palette: &Palette, phosphor: &mut Phosphor) {
+    let screen_buffer = phosphor.apply(emu.get_display());
+    texture
+        .with_lock(None, |pixels, pitch| {
+            for (i, intensity) in screen_buffer.iter().enumerate() {
+                let color = palette.shade(*intensity);
 
-    let screen_buffer = emu.get_display();
-    canvas.set_draw_color(Color::RGB(0, 255, 0));
-    for (i, pixel) in screen_buffer.iter().enumerate() {
-        if *pixel {
-            // Convert our 1D array's index into a 2D (x,y) position
-            let x = (i % SCREEN_WIDTH) as u32;
-            let y = (i / SCREEN_WIDTH) as u32;
+                // Convert our 1D array's index into a 2D (x,y) position
+                let x = i % SCREEN_WIDTH;
+                let y = i / SCREEN_WIDTH;
 
-            let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
-            canvas.fill_rect(rect).unwrap();
-        }
-    }
+                let offset = y * pitch + x * 3;
+                pixels[offset..offset + 3].copy_from_slice(&[color.r, color.g, color.b]);
+            }
+        })
+        .unwrap();
+}
 
-    canvas.present();
+// Custom palettes are not part of the cycle, so they continue from the first built-in one
+fn next_palette(current: &Palette) -> Palette {
+    let position = PALETTES
+        .iter()
+        .position(|palette| palette.name == current.name);
+
+    match position {
+        Some(i) => PALETTES[(i + 1) % PALETTES.len()],
+        None => PALETTES[0],
+    }
 }
 
-fn key_to_button(key: Keycode) -> Option<usize> {
+fn key_to_button(key: Keycode, extra_keys: &[(Keycode, usize)]) -> Option<usize> {
+    let extra = extra_keys.iter().find(|(keycode, _)| *keycode == key);
+    if let Some((_, button)) = extra {
+        return Some(*button);
+    }
+
     match key {
         Keycode::Num1 => Some(0x1),
         Keycode::Num2 => Some(0x2),
@@ -0,0 +1,212 @@
+use core::Quirks;
+
+use crate::filter::Filter;
+use crate::palette::{self, Palette};
+
+pub const USAGE: &str = "Usage: cargo run -- [options] <path-to-game>
+
+Games are CHIP-8 ROMs, Octo source files (.8o) or Octo cartridges (.gif).
+
+Options:
+  --palette <name>     green, amber, white, lcd or octo
+  --colors <hex,...>   custom palette: background, foreground and up to two XO-CHIP colours
+  --filter <name>      anti-flicker filter: none, decay or blend
+  --quirks <name,...>  enable interpreter quirks: display-wait, key-release
+  --gdb <port>         wait for a GDB remote debugger on localhost:<port>
+  --script <file>      run a Rhai script alongside the game
+  --cheats <file>      cheat file to use, instead of <path-to-game>.cht
+  --ticks <count>      instructions run per frame
+  --romdb <file>       ROM database to use instead of the bundled one, in the format of the
+                       community CHIP-8 database's programs.json
+  --disassemble        print the disassembly, telling code from data, and exit
+  --cfg <file>         write the control-flow graph in Graphviz DOT format, and exit
+  --frames <count>     run for this many frames without a window, e.g. for scripted tests
+  --profile            print the hottest instructions, opcodes and subroutines when done
+  --folded <file>      write the profiled call stacks in flamegraph's folded format when done
+  --coverage <file>    write a disassembly marking the code that never ran when done
+  --selfmod            log writes to code that already ran, and code run from written data
+  --recompile          run translated blocks instead of interpreting, unless an option or
+                       script needs to see every instruction
+
+Palette, quirks and ticks default to the cartridge's options, or the ROM database's
+recommendations for known games.";
+
+pub struct Options {
+    pub rom: String,
+    pub palette: Option<Palette>,
+    pub filter: Filter,
+    pub quirks: Option<Quirks>,
+    pub gdb_port: Option<u16>,
+    pub script: Option<String>,
+    pub cheats: Option<String>,
+    pub ticks_per_frame: Option<usize>,
+    pub romdb: Option<String>,
+    pub disassemble: bool,
+    pub cfg: Option<String>,
+    pub frames: Option<u64>,
+    pub profile: bool,
+    pub folded: Option<String>,
+    pub coverage: Option<String>,
+    pub selfmod: bool,
+    pub recompile: bool,
+}
+
+impl Options {
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut rom = None;
+        let mut palette = None;
+        let mut filter = Filter::None;
+        let mut quirks = None;
+        let mut gdb_port = None;
+        let mut script = None;
+        let mut cheats = None;
+        let mut ticks_per_frame = None;
+        let mut romdb = None;
+        let mut disassemble = false;
+        let mut cfg = None;
+        let mut frames = None;
+        let mut profile = false;
+        let mut folded = None;
+        let mut coverage = None;
+        let mut selfmod = false;
+        let mut recompile = false;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--palette" => {
+                    let name = value(&mut args, arg)?;
+                    palette = Some(
+                        palette::by_name(name)
+                            .ok_or_else(|| format!("Unknown palette \"{}\".", name))?,
+                    );
+                }
+                "--colors" => palette = Some(Palette::from_hex_list(value(&mut args, arg)?)?),
+                "--filter" => {
+                    let name = value(&mut args, arg)?;
+                    filter = Filter::by_name(name)
+                        .ok_or_else(|| format!("Unknown filter \"{}\".", name))?;
+                }
+                "--quirks" => quirks = Some(parse_quirks(value(&mut args, arg)?)?),
+                "--gdb" => {
+                    let port = value(&mut args, arg)?;
+                    gdb_port = Some(
+                        port.parse()
+                            .map_err(|_| format!("Invalid port \"{}\".", port))?,
+                    );
+                }
+                "--script" => script = Some(value(&mut args, arg)?.to_string()),
+                "--cheats" => cheats = Some(value(&mut args, arg)?.to_string()),
+                "--ticks" => {
+                    let ticks = value(&mut args, arg)?;
+                    ticks_per_frame = Some(
+                        ticks
+                            .parse()
+                            .map_err(|_| format!("Invalid tick count \"{}\".", ticks))?,
+                    );
+                }
+                "--romdb" => romdb = Some(value(&mut args, arg)?.to_string()),
+                "--disassemble" => disassemble = true,
+                "--cfg" => cfg = Some(value(&mut args, arg)?.to_string()),
+                "--frames" => {
+                    let count = value(&mut args, arg)?;
+                    frames = Some(
+                        count
+                            .parse()
+                            .map_err(|_| format!("Invalid frame count \"{}\".", count))?,
+                    );
+                }
+                "--profile" => profile = true,
+                "--folded" => folded = Some(value(&mut args, arg)?.to_string()),
+                "--coverage" => coverage = Some(value(&mut args, arg)?.to_string()),
+                "--selfmod" => selfmod = true,
+                "--recompile" => recompile = true,
+                _ if arg.starts_with("--") => return Err(format!("Unknown option \"{}\".", arg)),
+                _ if rom.is_none() => rom = Some(arg.clone()),
+                _ => return Err(format!("Unexpected argument \"{}\".", arg)),
+            }
+        }
+
+        Ok(Options {
+            rom: rom.ok_or("Missing path to the game.")?,
+            palette,
+            filter,
+            quirks,
+            gdb_port,
+            script,
+            cheats,
+            ticks_per_frame,
+            romdb,
+            disassemble,
+            cfg,
+            frames,
+            profile,
+            folded,
+            coverage,
+            selfmod,
+            recompile,
+        })
+    }
+}
+
+fn value<'a>(args: &mut impl Iterator<Item = &'a String>, option: &str) -> Result<&'a str, String> {
+    args.next()
+        .map(|value| value.as_str())
+        .ok_or_else(|| format!("Missing value for \"{}\".", option))
+}
+
+fn parse_quirks(list: &str) -> Result<Quirks, String> {
+    let mut quirks = Quirks::default();
+    for name in list.split(',') {
+        match name.trim() {
+            "display-wait" => quirks.display_wait = true,
+            "key-release" => quirks.key_release = true,
+            _ => return Err(format!("Unknown quirk \"{}\".", name)),
+        }
+    }
+    Ok(quirks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn palettes_are_named_or_listed() {
+        let options = parse(&["--palette", "amber", "game.ch8"]).unwrap();
+        assert_eq!(options.palette.unwrap().name, "amber");
+
+        let options = parse(&["--colors", "000000,FFFFFF", "game.ch8"]).unwrap();
+        assert_eq!(options.palette.unwrap().name, "custom");
+
+        let options = parse(&["game.ch8"]).unwrap();
+        assert!(options.palette.is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_palettes_and_bad_colours() {
+        assert_eq!(
+            parse(&["--palette", "purple", "game.ch8"]).err().unwrap(),
+            "Unknown palette \"purple\"."
+        );
+        assert_eq!(
+            parse(&["--colors", "000000,FFFFF", "game.ch8"])
+                .err()
+                .unwrap(),
+            "Invalid colour \"FFFFF\", expected RRGGBB."
+        );
+        assert_eq!(
+            parse(&["--colors", "000000", "game.ch8"]).err().unwrap(),
+            "Expected 2 to 4 colours, got 1 in \"000000\"."
+        );
+        assert_eq!(
+            parse(&["game.ch8", "--palette"]).err().unwrap(),
+            "Missing value for \"--palette\"."
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use core::SCREEN_WIDTH;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+/// Something drawn by a script on top of the game, in CHIP-8 pixel coordinates.
+pub enum Shape {
+    Text {
+        x: i32,
+        y: i32,
+        text: String,
+    },
+    Rect {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+}
+
+const OVERLAY_COLOR: Color = Color::RGB(0xFF, 0xFF, 0xFF);
+const GLYPH_WIDTH: i32 = 3;
+const GLYPH_HEIGHT: i32 = 5;
+
+pub fn draw(canvas: &mut Canvas<Window>, shapes: &[Shape]) {
+    // Shapes are placed on the CHIP-8 grid, but text gets finer pixels so it stays readable
+    let (window_width, _) = canvas.output_size().unwrap();
+    let scale = (window_width / SCREEN_WIDTH as u32) as i32;
+    let dot = (scale / GLYPH_HEIGHT).max(1);
+
+    canvas.set_draw_color(OVERLAY_COLOR);
+    for shape in shapes {
+        match shape {
+            Shape::Text { x, y, text } => {
+                let mut left = x * scale;
+                for c in text.chars() {
+                    for (row, bits) in glyph(c).iter().enumerate() {
+                        for column in 0..GLYPH_WIDTH {
+                            if bits & (0b100 >> column) != 0 {
+                                let rect = Rect::new(
+                                    left + column * dot,
+                                    y * scale + row as i32 * dot,
+                                    dot as u32,
+                                    dot as u32,
+                                );
+                                canvas.fill_rect(rect).unwrap();
+                            }
+                        }
+                    }
+                    left += (GLYPH_WIDTH + 1) * dot;
+                }
+            }
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let rect = Rect::new(
+                    x * scale,
+                    y * scale,
+                    width * scale as u32,
+                    height * scale as u32,
+                );
+                canvas.draw_rect(rect).unwrap();
+            }
+        }
+    }
+}
+
+// 3x5 font, one row per byte using its lowest 3 bits
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [7, 5, 5, 5, 7],
+        '1' => [2, 6, 2, 2, 7],
+        '2' => [7, 1, 7, 4, 7],
+        '3' => [7, 1, 7, 1, 7],
+        '4' => [5, 5, 7, 1, 1],
+        '5' => [7, 4, 7, 1, 7],
+        '6' => [7, 4, 7, 5, 7],
+        '7' => [7, 1, 2, 4, 4],
+        '8' => [7, 5, 7, 5, 7],
+        '9' => [7, 5, 7, 1, 7],
+        'A' => [2, 5, 7, 5, 5],
+        'B' => [6, 5, 6, 5, 6],
+        'C' => [3, 4, 4, 4, 3],
+        'D' => [6, 5, 5, 5, 6],
+        'E' => [7, 4, 6, 4, 7],
+        'F' => [7, 4, 6, 4, 4],
+        'G' => [3, 4, 5, 5, 3],
+        'H' => [5, 5, 7, 5, 5],
+        'I' => [7, 2, 2, 2, 7],
+        'J' => [1, 1, 1, 5, 2],
+        'K' => [5, 5, 6, 5, 5],
+        'L' => [4, 4, 4, 4, 7],
+        'M' => [5, 7, 7, 5, 5],
+        'N' => [6, 5, 5, 5, 5],
+        'O' => [2, 5, 5, 5, 2],
+        'P' => [6, 5, 6, 4, 4],
+        'Q' => [2, 5, 5, 6, 3],
+        'R' => [6, 5, 6, 5, 5],
+        'S' => [3, 4, 2, 1, 6],
+        'T' => [7, 2, 2, 2, 2],
+        'U' => [5, 5, 5, 5, 7],
+        'V' => [5, 5, 5, 5, 2],
+        'W' => [5, 5, 7, 7, 5],
+        'X' => [5, 5, 2, 5, 5],
+        'Y' => [5, 5, 2, 2, 2],
+        'Z' => [7, 1, 2, 4, 7],
+        ' ' => [0, 0, 0, 0, 0],
+        '.' => [0, 0, 0, 0, 2],
+        ',' => [0, 0, 0, 2, 4],
+        ':' => [0, 2, 0, 2, 0],
+        '-' => [0, 0, 7, 0, 0],
+        '+' => [0, 2, 7, 2, 0],
+        '=' => [0, 7, 0, 7, 0],
+        '/' => [1, 1, 2, 4, 4],
+        '!' => [2, 2, 2, 0, 2],
+        '(' => [1, 2, 2, 2, 1],
+        ')' => [4, 2, 2, 2, 4],
+        '<' => [1, 2, 4, 2, 1],
+        '>' => [4, 2, 1, 2, 4],
+        '#' => [5, 7, 5, 7, 5],
+        '%' => [5, 1, 2, 4, 5],
+        '_' => [0, 0, 0, 0, 7],
+        '\'' => [2, 2, 0, 0, 0],
+        _ => [6, 1, 2, 0, 2], // '?'
+    }
+}
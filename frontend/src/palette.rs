@@ -0,0 +1,184 @@
+use sdl2::pixels::Color;
+
+/// A set of display colours. Index 0 is the background and index 1 the foreground; indices 2
+/// and 3 are reserved for XO-CHIP, where the second bitplane and the overlap of both planes get
+/// their own colours.
+#[derive(Clone, Copy)]
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: [Color; 4],
+}
+
+impl Palette {
+    pub fn background(&self) -> Color {
+        self.colors[0]
+    }
+
+    pub fn foreground(&self) -> Color {
+        self.colors[1]
+    }
+
+    /// Mixes the background and foreground, `intensity` being how lit the pixel is.
+    pub fn shade(&self, intensity: f32) -> Color {
+        let mix = |bg: u8, fg: u8| (bg as f32 + (fg as f32 - bg as f32) * intensity) as u8;
+        let (bg, fg) = (self.background(), self.foreground());
+        Color::RGB(mix(bg.r, fg.r), mix(bg.g, fg.g), mix(bg.b, fg.b))
+    }
+
+    /// Builds a palette from a comma separated list of 2 to 4 hex colours, e.g. `000000,33ff66`.
+    /// Missing bitplane colours fall back to the foreground.
+    pub fn from_hex_list(list: &str) -> Result<Palette, String> {
+        let colors = list
+            .split(',')
+            .map(parse_hex)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if colors.len() < 2 || colors.len() > 4 {
+            return Err(format!(
+                "Expected 2 to 4 colours, got {} in \"{}\".",
+                colors.len(),
+                list
+            ));
+        }
+
+        let foreground = colors[1];
+        Ok(Palette {
+            name: "custom",
+            colors: [
+                colors[0],
+                foreground,
+                colors.get(2).copied().unwrap_or(foreground),
+                colors.get(3).copied().unwrap_or(foreground),
+            ],
+        })
+    }
+}
+
+pub const PALETTES: [Palette; 5] = [
+    Palette {
+        name: "green",
+        colors: [
+            Color::RGB(0x00, 0x00, 0x00),
+            Color::RGB(0x00, 0xFF, 0x00),
+            Color::RGB(0x00, 0x99, 0x00),
+            Color::RGB(0x99, 0xFF, 0x99),
+        ],
+    },
+    Palette {
+        name: "amber",
+        colors: [
+            Color::RGB(0x1A, 0x0F, 0x00),
+            Color::RGB(0xFF, 0xB0, 0x00),
+            Color::RGB(0x99, 0x66, 0x00),
+            Color::RGB(0xFF, 0xDD, 0x88),
+        ],
+    },
+    Palette {
+        name: "white",
+        colors: [
+            Color::RGB(0x00, 0x00, 0x00),
+            Color::RGB(0xFF, 0xFF, 0xFF),
+            Color::RGB(0xAA, 0xAA, 0xAA),
+            Color::RGB(0x55, 0x55, 0x55),
+        ],
+    },
+    Palette {
+        name: "lcd",
+        colors: [
+            Color::RGB(0x9B, 0xBC, 0x0F),
+            Color::RGB(0x0F, 0x38, 0x0F),
+            Color::RGB(0x30, 0x62, 0x30),
+            Color::RGB(0x8B, 0xAC, 0x0F),
+        ],
+    },
+    // Same colours Octo uses out of the box
+    Palette {
+        name: "octo",
+        colors: [
+            Color::RGB(0x99, 0x66, 0x00),
+            Color::RGB(0xFF, 0xCC, 0x00),
+            Color::RGB(0xFF, 0x66, 0x00),
+            Color::RGB(0x66, 0x22, 0x00),
+        ],
+    },
+];
+
+pub fn by_name(name: &str) -> Option<Palette> {
+    PALETTES
+        .iter()
+        .find(|palette| palette.name.eq_ignore_ascii_case(name))
+        .copied()
+}
+
+/// Parses a `RRGGBB` colour, with or without a leading `#`.
+pub fn parse_hex(hex: &str) -> Result<Color, String> {
+    let digits = hex.trim().trim_start_matches('#');
+    if digits.len() != 6 {
+        return Err(format!("Invalid colour \"{}\", expected RRGGBB.", hex));
+    }
+
+    let value =
+        u32::from_str_radix(digits, 16).map_err(|_| format!("Invalid colour \"{}\".", hex))?;
+    Ok(Color::RGB(
+        (value >> 16) as u8,
+        (value >> 8) as u8,
+        value as u8,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_palettes_fill_in_the_xo_chip_colours() {
+        let palette = Palette::from_hex_list("#102030, 405060").unwrap();
+        assert_eq!(palette.name, "custom");
+        let foreground = Color::RGB(0x40, 0x50, 0x60);
+        assert_eq!(
+            palette.colors,
+            [
+                Color::RGB(0x10, 0x20, 0x30),
+                foreground,
+                foreground,
+                foreground
+            ]
+        );
+
+        let palette = Palette::from_hex_list("000000,111111,222222,333333").unwrap();
+        assert_eq!(palette.colors[2], Color::RGB(0x22, 0x22, 0x22));
+        assert_eq!(palette.colors[3], Color::RGB(0x33, 0x33, 0x33));
+    }
+
+    #[test]
+    fn rejects_malformed_colours() {
+        assert_eq!(
+            Palette::from_hex_list("000000,12345").err().unwrap(),
+            "Invalid colour \"12345\", expected RRGGBB."
+        );
+        assert_eq!(
+            Palette::from_hex_list("GG0000,000000").err().unwrap(),
+            "Invalid colour \"GG0000\"."
+        );
+    }
+
+    #[test]
+    fn needs_two_to_four_colours() {
+        assert_eq!(
+            Palette::from_hex_list("000000").err().unwrap(),
+            "Expected 2 to 4 colours, got 1 in \"000000\"."
+        );
+        let five = ["000000"; 5].join(",");
+        assert_eq!(
+            Palette::from_hex_list(&five).err().unwrap(),
+            format!("Expected 2 to 4 colours, got 5 in \"{}\".", five)
+        );
+    }
+
+    #[test]
+    fn finds_built_in_palettes_by_name() {
+        assert_eq!(by_name("amber").unwrap().name, "amber");
+        assert_eq!(by_name("LCD").unwrap().name, "lcd");
+        assert!(by_name("custom").is_none());
+    }
+}
@@ -0,0 +1,234 @@
+use core::Quirks;
+
+use sdl2::keyboard::Keycode;
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::fs;
+
+use crate::palette::Palette;
+
+// The community CHIP-8 database (https://github.com/chip-8/chip-8-database), whose programs.json
+// can be replaced by a newer one as is
+const BUNDLED: &str = include_str!("../data/programs.json");
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, PlatformQuirks>,
+    tickrate: Option<usize>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    colors: Option<Colors>,
+}
+
+#[derive(Deserialize)]
+struct PlatformQuirks {
+    vblank: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+/// Settings recommended for a known ROM.
+pub struct Entry {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub platform: Option<String>,
+    pub quirks: Quirks,
+    pub ticks_per_frame: Option<usize>,
+    pub keys: Vec<(Keycode, usize)>,
+    pub palette: Option<Palette>,
+}
+
+/// Looks the ROM up by its SHA-1, in the database at `path` or the bundled one.
+pub fn lookup(path: Option<&str>, rom: &[u8]) -> Result<Option<Entry>, String> {
+    let json = match path {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read {}: {}", path, error))?,
+        None => BUNDLED.to_string(),
+    };
+    let programs: Vec<Program> =
+        serde_json::from_str(&json).map_err(|error| format!("Invalid ROM database: {}", error))?;
+
+    let hash = sha1_smol::Sha1::from(rom).digest().to_string();
+    for program in programs {
+        if let Some(rom) = program.roms.get(&hash) {
+            return Ok(Some(entry(&program, rom)));
+        }
+    }
+    Ok(None)
+}
+
+fn entry(program: &Program, rom: &Rom) -> Entry {
+    // The first platform listed is the one the ROM was written for
+    let platform = rom.platforms.first().cloned();
+    let default_vblank = matches!(platform.as_deref(), Some("originalChip8" | "hybridVIP"));
+    let vblank = platform
+        .as_ref()
+        .and_then(|platform| rom.quirky_platforms.get(platform))
+        .and_then(|quirks| quirks.vblank)
+        .unwrap_or(default_vblank);
+
+    Entry {
+        title: program.title.clone(),
+        authors: program.authors.clone(),
+        quirks: Quirks {
+            display_wait: vblank,
+            // The VIP interpreter waits for the key to be released too
+            key_release: default_vblank,
+        },
+        platform,
+        ticks_per_frame: rom.tickrate,
+        keys: rom
+            .keys
+            .iter()
+            .filter_map(|(name, &key)| Some((keycode(name)?, key as usize & 0xF)))
+            .collect(),
+        palette: rom
+            .colors
+            .as_ref()
+            .and_then(|colors| Palette::from_hex_list(&colors.pixels.join(",")).ok()),
+    }
+}
+
+// The database names the game's controls, give them the keys one would expect
+fn keycode(name: &str) -> Option<Keycode> {
+    match name {
+        "up" => Some(Keycode::Up),
+        "down" => Some(Keycode::Down),
+        "left" => Some(Keycode::Left),
+        "right" => Some(Keycode::Right),
+        "a" => Some(Keycode::Space),
+        "b" => Some(Keycode::LShift),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    const ROM: [u8; 2] = [0x12, 0x00]; // JMP 0x200
+
+    // A database with two versions of the same game, the first one for the VIP
+    fn database(name: &str) -> String {
+        let hash = sha1_smol::Sha1::from(ROM).digest().to_string();
+        let json = format!(
+            r##"[{{
+                "title": "Loop",
+                "authors": ["Someone"],
+                "roms": {{
+                    "{}": {{
+                        "platforms": ["originalChip8", "superchip"],
+                        "tickrate": 15,
+                        "keys": {{"up": 5, "a": 6, "start": 7}},
+                        "colors": {{"pixels": ["#000000", "#00FF00"]}}
+                    }},
+                    "0000000000000000000000000000000000000000": {{
+                        "platforms": ["superchip"]
+                    }}
+                }}
+            }}]"##,
+            hash
+        );
+        let path = env::temp_dir().join(format!("chip-r-{}-{}.json", name, std::process::id()));
+        fs::write(&path, json).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn finds_roms_by_hash() {
+        let path = database("hash");
+        let entry = lookup(Some(&path), &ROM).unwrap().unwrap();
+        let unknown = lookup(Some(&path), &[0x00, 0xE0]).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(entry.title, "Loop");
+        assert_eq!(entry.authors, ["Someone"]);
+        assert_eq!(entry.platform.as_deref(), Some("originalChip8"));
+        assert_eq!(entry.ticks_per_frame, Some(15));
+        assert_eq!(entry.palette.unwrap().foreground().g, 0xFF);
+        let mut keys = entry.keys;
+        keys.sort_by_key(|&(_, key)| key);
+        assert_eq!(keys, [(Keycode::Up, 5), (Keycode::Space, 6)]);
+        assert!(unknown.is_none());
+    }
+
+    #[test]
+    fn platforms_set_the_quirks() {
+        let program = Program {
+            title: String::new(),
+            authors: Vec::new(),
+            roms: HashMap::new(),
+        };
+        let quirks = |json: &str| entry(&program, &serde_json::from_str(json).unwrap()).quirks;
+
+        let vip = Quirks {
+            display_wait: true,
+            key_release: true,
+        };
+        assert_eq!(quirks(r#"{"platforms": ["originalChip8"]}"#), vip);
+        assert_eq!(quirks(r#"{"platforms": ["hybridVIP", "chip8"]}"#), vip);
+        assert_eq!(quirks(r#"{"platforms": ["superchip"]}"#), Quirks::default());
+        assert_eq!(quirks(r#"{}"#), Quirks::default());
+
+        // Per-platform overrides only count for the ROM's own platform
+        let quirky = r#"{"platforms": ["originalChip8"],
+                         "quirkyPlatforms": {"originalChip8": {"vblank": false}}}"#;
+        assert_eq!(
+            quirks(quirky),
+            Quirks {
+                display_wait: false,
+                key_release: true,
+            }
+        );
+        let elsewhere = r#"{"platforms": ["superchip"],
+                            "quirkyPlatforms": {"originalChip8": {"vblank": true}}}"#;
+        assert_eq!(quirks(elsewhere), Quirks::default());
+    }
+
+    #[test]
+    fn custom_database_replaces_the_bundled_one() {
+        assert!(lookup(None, &ROM).unwrap().is_none());
+
+        let path = database("override");
+        let entry = lookup(Some(&path), &ROM).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(entry.is_some());
+    }
+
+    #[test]
+    fn reports_unreadable_databases() {
+        let missing = env::temp_dir().join("chip-r-no-such-database.json");
+        let missing = missing.to_string_lossy();
+        let error = lookup(Some(&missing), &ROM).err().unwrap();
+        assert!(
+            error.starts_with(&format!("Unable to read {}", missing)),
+            "{}",
+            error
+        );
+
+        let path = database("invalid");
+        fs::write(&path, "{").unwrap();
+        let error = lookup(Some(&path), &ROM).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(error.starts_with("Invalid ROM database: "), "{}", error);
+    }
+}
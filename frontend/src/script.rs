@@ -0,0 +1,346 @@
+use core::{Emulator, NUM_KEYS, NUM_REGS, RAM_SIZE};
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, INT};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::overlay::Shape;
+
+/// A Rhai script hooked into the emulator. It may define these functions, all optional:
+///
+/// - `on_frame()`: called at the end of every frame.
+/// - `on_instruction(pc, opcode)`: called before every instruction.
+///
+/// Rhai functions can't see the script's global variables, so both run with `this` bound to
+/// an object map that persists between calls, e.g. `this.score = peek(0x3F0)`.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Dynamic,
+    context: Rc<RefCell<Context>>,
+    has_on_frame: bool,
+    has_on_instruction: bool,
+}
+
+// The script can't borrow the emulator, so it works on a copy of the machine taken before each
+// call, the RAM only when it changed since. Its changes are applied to the copy right away, so
+// the script reads its own writes, and queued to be applied to the emulator once the call returns.
+struct Context {
+    registers: [u8; NUM_REGS],
+    pc: u16,
+    i: u16,
+    ram: Vec<u8>,
+    ram_version: Option<u32>, // Emulator::ram_version when the RAM was copied
+    frame: INT,
+    changes: Vec<Change>,
+    overlay: Vec<Shape>, // drawn during the current frame
+    shown: Vec<Shape>,   // drawn during the last complete frame
+}
+
+enum Change {
+    Register(usize, u8),
+    I(u16),
+    Memory(usize, u8),
+    Key(usize, bool),
+}
+
+type Result<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+impl Script {
+    pub fn load(path: &str) -> std::result::Result<Script, String> {
+        let context = Rc::new(RefCell::new(Context {
+            registers: [0; NUM_REGS],
+            pc: 0,
+            i: 0,
+            ram: vec![0; RAM_SIZE],
+            ram_version: None,
+            frame: 0,
+            changes: Vec::new(),
+            overlay: Vec::new(),
+            shown: Vec::new(),
+        }));
+
+        let mut engine = Engine::new();
+        register_api(&mut engine, &context);
+
+        let ast = engine
+            .compile_file(path.into())
+            .map_err(|error| format!("Unable to load script: {}", error))?;
+        let has_function = |name: &str| ast.iter_functions().any(|f| f.name == name);
+        let has_on_frame = has_function("on_frame");
+        let has_on_instruction = has_function("on_instruction");
+
+        // Top level statements run once, e.g. to print instructions
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|error| format!("Script failed: {}", error))?;
+
+        Ok(Script {
+            engine,
+            ast,
+            scope,
+            state: Dynamic::from_map(Map::new()),
+            context,
+            has_on_frame,
+            has_on_instruction,
+        })
+    }
+
+    /// Shapes drawn by the script during the last frame.
+    pub fn overlay(&self) -> std::cell::Ref<'_, Vec<Shape>> {
+        std::cell::Ref::map(self.context.borrow(), |context| &context.shown)
+    }
+
+    pub fn has_on_instruction(&self) -> bool {
+        self.has_on_instruction
+    }
+
+    pub fn on_instruction(&mut self, emu: &mut Emulator) -> std::result::Result<(), String> {
+        if !self.has_on_instruction {
+            return Ok(());
+        }
+
+        let pc = emu.pc() as usize;
+        let opcode = emu
+            .memory(pc..(pc + 2).min(RAM_SIZE))
+            .iter()
+            .fold(0, |op, &byte| (op << 8) | byte as INT);
+        self.call(emu, "on_instruction", (pc as INT, opcode))
+    }
+
+    pub fn on_frame(&mut self, emu: &mut Emulator) -> std::result::Result<(), String> {
+        self.context.borrow_mut().frame += 1;
+        let result = if self.has_on_frame {
+            self.call(emu, "on_frame", ())
+        } else {
+            Ok(())
+        };
+
+        let mut context = self.context.borrow_mut();
+        context.shown = std::mem::take(&mut context.overlay);
+        result
+    }
+
+    fn call(
+        &mut self,
+        emu: &mut Emulator,
+        name: &str,
+        args: impl rhai::FuncArgs,
+    ) -> std::result::Result<(), String> {
+        {
+            let mut context = self.context.borrow_mut();
+            context.registers = *emu.registers();
+            context.pc = emu.pc();
+            context.i = emu.i();
+            if context.ram_version != Some(emu.ram_version()) {
+                context.ram.copy_from_slice(emu.memory(0..RAM_SIZE));
+                context.ram_version = Some(emu.ram_version());
+            }
+        }
+
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut self.state);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            name,
+            args,
+        );
+
+        for change in self.context.borrow_mut().changes.drain(..) {
+            match change {
+                Change::Register(register, value) => emu.set_register(register, value),
+                // Checked by set_i already
+                Change::I(value) => {
+                    emu.set_i(value).ok();
+                }
+                Change::Memory(address, value) => emu.write_memory(address, &[value]),
+                Change::Key(key, pressed) => emu.keypress(key, pressed),
+            }
+        }
+
+        result
+            .map(|_| ())
+            .map_err(|error| format!("Script failed in {}: {}", name, error))
+    }
+}
+
+fn register_api(engine: &mut Engine, context: &Rc<RefCell<Context>>) {
+    let ctx = context.clone();
+    engine.register_fn("reg", move |register: INT| -> Result<INT> {
+        let register = check(register, NUM_REGS, "register")?;
+        Ok(ctx.borrow().registers[register] as INT)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("set_reg", move |register: INT, value: INT| -> Result<()> {
+        let register = check(register, NUM_REGS, "register")?;
+        let mut context = ctx.borrow_mut();
+        context.registers[register] = value as u8;
+        context
+            .changes
+            .push(Change::Register(register, value as u8));
+        Ok(())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("peek", move |address: INT| -> Result<INT> {
+        let address = check(address, RAM_SIZE, "address")?;
+        Ok(ctx.borrow().ram[address] as INT)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("poke", move |address: INT, value: INT| -> Result<()> {
+        let address = check(address, RAM_SIZE, "address")?;
+        let mut context = ctx.borrow_mut();
+        context.ram[address] = value as u8;
+        context.changes.push(Change::Memory(address, value as u8));
+        Ok(())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("pc", move || ctx.borrow().pc as INT);
+
+    let ctx = context.clone();
+    engine.register_fn("i", move || ctx.borrow().i as INT);
+
+    let ctx = context.clone();
+    engine.register_fn("set_i", move |value: INT| -> Result<()> {
+        let value = check(value, RAM_SIZE, "address")? as u16;
+        let mut context = ctx.borrow_mut();
+        context.i = value;
+        context.changes.push(Change::I(value));
+        Ok(())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("frame", move || ctx.borrow().frame);
+
+    for (name, pressed) in [("press", true), ("release", false)] {
+        let ctx = context.clone();
+        engine.register_fn(name, move |key: INT| -> Result<()> {
+            let key = check(key, NUM_KEYS, "key")?;
+            ctx.borrow_mut().changes.push(Change::Key(key, pressed));
+            Ok(())
+        });
+    }
+
+    let ctx = context.clone();
+    engine.register_fn("text", move |x: INT, y: INT, text: Dynamic| {
+        ctx.borrow_mut().overlay.push(Shape::Text {
+            x: x as i32,
+            y: y as i32,
+            text: text.to_string(),
+        });
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("rect", move |x: INT, y: INT, width: INT, height: INT| {
+        ctx.borrow_mut().overlay.push(Shape::Rect {
+            x: x as i32,
+            y: y as i32,
+            width: width.max(0) as u32,
+            height: height.max(0) as u32,
+        });
+    });
+}
+
+fn check(value: INT, limit: usize, what: &str) -> Result<usize> {
+    if value < 0 || value as usize >= limit {
+        return Err(format!("Invalid {} {}", what, value).into());
+    }
+    Ok(value as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    const SCRIPT: &str = r#"
+        fn on_instruction(pc, opcode) {
+            text(0, 0, `${pc} ${opcode}`);
+        }
+
+        fn on_frame() {
+            poke(0x300, peek(0x300) + 1);
+            set_reg(2, peek(0x300) * 2);
+            press(5);
+            set_i(0x123);
+            rect(0, 0, 4, frame());
+        }
+    "#;
+
+    // Writes the script to a file named after the test, as Script only loads files
+    fn load(name: &str, source: &str) -> Script {
+        let path = env::temp_dir().join(format!("chip-r-{}-{}.rhai", name, std::process::id()));
+        fs::write(&path, source).unwrap();
+        let script = Script::load(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        script.unwrap()
+    }
+
+    #[test]
+    fn changes_reach_the_emulator() {
+        let mut script = load("changes", SCRIPT);
+        let mut emu = Emulator::new();
+        emu.write_memory(0x300, &[7]);
+
+        script.on_frame(&mut emu).unwrap();
+        assert_eq!(emu.memory(0x300..0x301), [8]);
+        // The script reads its own poke
+        assert_eq!(emu.registers()[2], 16);
+        assert!(emu.keys()[5]);
+        assert_eq!(emu.i(), 0x123);
+
+        // RAM changed behind the script's back is seen on the next call
+        emu.write_memory(0x300, &[50]);
+        script.on_frame(&mut emu).unwrap();
+        assert_eq!(emu.memory(0x300..0x301), [51]);
+    }
+
+    #[test]
+    fn overlay_shows_the_last_complete_frame() {
+        let mut script = load("overlay", SCRIPT);
+        let mut emu = Emulator::new();
+        emu.load(&[0x12, 0x00]); // JMP 0x200
+
+        script.on_instruction(&mut emu).unwrap();
+        assert!(script.overlay().is_empty());
+
+        script.on_frame(&mut emu).unwrap();
+        let overlay = script.overlay();
+        assert_eq!(overlay.len(), 2);
+        assert!(matches!(&overlay[0], Shape::Text { text, .. } if text == "512 4608"));
+        assert!(matches!(overlay[1], Shape::Rect { height: 1, .. }));
+        drop(overlay);
+
+        script.on_frame(&mut emu).unwrap();
+        let overlay = script.overlay();
+        assert_eq!(overlay.len(), 1);
+        assert!(matches!(overlay[0], Shape::Rect { height: 2, .. }));
+    }
+
+    #[test]
+    fn rejects_out_of_range_arguments() {
+        let mut emu = Emulator::new();
+        for (name, call, error) in [
+            ("i", "set_i(0x1000)", "Invalid address 4096"),
+            ("poke", "poke(-1, 0)", "Invalid address -1"),
+            ("reg", "set_reg(16, 0)", "Invalid register 16"),
+            ("key", "press(16)", "Invalid key 16"),
+        ] {
+            let mut script = load(name, &format!("fn on_frame() {{ {}; }}", call));
+            let message = script.on_frame(&mut emu).err().unwrap();
+            assert!(message.contains(error), "{}", message);
+        }
+        assert_eq!(emu.i(), 0);
+    }
+}
@@ -0,0 +1,110 @@
+use core::cheat::{Freezer, RamSearch};
+use core::coverage::Coverage;
+use core::gdb::GdbStub;
+use core::profile::Profiler;
+use core::recompiler::Recompiler;
+use core::selfmod::Detector;
+use core::{DecodeError, Emulator};
+
+use crate::script::Script;
+
+/// The emulator and everything hooked into it, run one frame at a time by the window or, with
+/// `--frames`, without one.
+pub struct Session {
+    pub emu: Emulator,
+    pub ticks_per_frame: usize,
+    pub freezer: Freezer,
+    pub cheats_enabled: bool,
+    pub search: Option<RamSearch>,
+    pub script: Option<Script>,
+    pub gdb: Option<GdbStub>,
+    pub profiler: Option<Profiler>,
+    pub coverage: Option<Coverage>,
+    pub selfmod: Option<Detector>,
+    pub recompiler: Option<Recompiler>,
+}
+
+impl Session {
+    pub fn frame(&mut self) {
+        if let Some(gdb) = self.gdb.as_mut() {
+            gdb.poll(&mut self.emu)
+                .expect("Debugger connection failed.");
+        }
+
+        // Refresh rate of drawing
+        let faulted = self.emu.fault().is_some();
+        let observed = self.observes_instructions();
+        match self.recompiler.as_mut() {
+            Some(recompiler) if !observed => recompiler.run(&mut self.emu, self.ticks_per_frame),
+            _ => self.interpret(),
+        }
+        // The emulator stays stuck on the opcode, so only tell once
+        if let (false, Some(DecodeError(op))) = (faulted, self.emu.fault()) {
+            println!(
+                "Unknown opcode {:04X} at {:03X}, stopped.",
+                op,
+                self.emu.pc()
+            );
+        }
+
+        // Time stands still while the debugger holds the emulator
+        if !self.is_halted() {
+            self.emu.tick_timers();
+            if self.cheats_enabled {
+                self.freezer.apply(&mut self.emu);
+            }
+            if let Some(Err(error)) = self.script.as_mut().map(|s| s.on_frame(&mut self.emu)) {
+                println!("{}", error);
+                self.script = None;
+            }
+        }
+    }
+
+    // Runs the frame's instructions one at a time, letting everything hooked in see each of them
+    fn interpret(&mut self) {
+        for _ in 0..self.ticks_per_frame {
+            if self.is_halted() {
+                break;
+            }
+            if let Some(Err(error)) = self
+                .script
+                .as_mut()
+                .map(|s| s.on_instruction(&mut self.emu))
+            {
+                println!("{}", error);
+                self.script = None;
+            }
+            if let Some(profiler) = self.profiler.as_mut() {
+                profiler.record(&self.emu);
+            }
+            if let Some(coverage) = self.coverage.as_mut() {
+                coverage.record(&self.emu);
+            }
+            if let Some(selfmod) = self.selfmod.as_mut() {
+                for event in selfmod.record(&self.emu) {
+                    println!("{}", event);
+                }
+            }
+            match self.gdb.as_mut() {
+                Some(gdb) => gdb
+                    .tick(&mut self.emu)
+                    .expect("Debugger connection failed."),
+                None => self.emu.tick(),
+            }
+        }
+    }
+
+    // The recompiler doesn't stop between instructions, so it can't run alongside anything
+    // looking at each of them
+    fn observes_instructions(&self) -> bool {
+        self.script.as_ref().is_some_and(Script::has_on_instruction)
+            || self.gdb.is_some()
+            || self.profiler.is_some()
+            || self.coverage.is_some()
+            || self.selfmod.is_some()
+    }
+
+    fn is_halted(&self) -> bool {
+        self.gdb.as_ref().is_some_and(GdbStub::is_halted)
+    }
+}
@@ -0,0 +1,482 @@
+mod ffi;
+
+use core::*;
+use ffi::*;
+
+use std::array;
+use std::ffi::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const TICKS_PER_FRAME: usize = 10; // same speed as the SDL frontend
+const MAX_ROM_SIZE: usize = 4096 - 0x200; // RAM left after the 0x200 program start
+const SAMPLE_RATE: f64 = 44100.0;
+const FOREGROUND: u32 = 0x0000_FF00;
+const BACKGROUND: u32 = 0x0000_0000;
+
+// The libretro API is a set of free functions, so the emulator lives in a global
+struct Core {
+    emu: Emulator,
+    rom: Vec<u8>,  // reset() wipes the RAM, so keep the program around to load it back
+    crashed: bool, // the emulator panicked, and stays stopped until reset or loaded
+}
+
+type Frame = [u32; SCREEN_WIDTH * SCREEN_HEIGHT];
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<retro_environment_t>,
+    video_refresh: Option<retro_video_refresh_t>,
+    audio_sample_batch: Option<retro_audio_sample_batch_t>,
+    input_poll: Option<retro_input_poll_t>,
+    input_state: Option<retro_input_state_t>,
+}
+
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> u32 {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {
+    *CORE.lock().unwrap() = Some(Core {
+        emu: Emulator::new(),
+        rom: Vec::new(),
+        crashed: false,
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *CORE.lock().unwrap() = None;
+}
+
+/// # Safety
+/// `info` must point to a writable `retro_system_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut retro_system_info) {
+    *info = retro_system_info {
+        library_name: c"chip-r".as_ptr(),
+        library_version: c"0.1.0".as_ptr(),
+        valid_extensions: c"ch8|c8".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// `info` must point to a writable `retro_system_av_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro_system_av_info) {
+    *info = retro_system_av_info {
+        geometry: retro_game_geometry {
+            base_width: SCREEN_WIDTH as u32,
+            base_height: SCREEN_HEIGHT as u32,
+            max_width: SCREEN_WIDTH as u32,
+            max_height: SCREEN_HEIGHT as u32,
+            aspect_ratio: SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32,
+        },
+        timing: retro_system_timing {
+            fps: 60.0,
+            sample_rate: SAMPLE_RATE,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: retro_environment_t) {
+    CALLBACKS.lock().unwrap().environment = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: retro_video_refresh_t) {
+    CALLBACKS.lock().unwrap().video_refresh = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: retro_audio_sample_t) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: retro_audio_sample_batch_t) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: retro_input_poll_t) {
+    CALLBACKS.lock().unwrap().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: retro_input_state_t) {
+    CALLBACKS.lock().unwrap().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: u32, _device: u32) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = CORE.lock().unwrap().as_mut() {
+        core.emu.reset();
+        core.emu.load(&core.rom);
+        core.crashed = false;
+    }
+}
+
+/// # Safety
+/// `game` must be null or point to a valid `retro_game_info` whose `data` holds `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const retro_game_info) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if let Some(environment) = callbacks().environment {
+        if !environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut format as *mut u32 as *mut c_void,
+        ) {
+            return false;
+        }
+    }
+
+    let rom = slice::from_raw_parts((*game).data as *const u8, (*game).size);
+    match CORE.lock().unwrap().as_mut() {
+        Some(core) if rom.len() <= MAX_ROM_SIZE => {
+            core.rom = rom.to_vec();
+            core.emu.reset();
+            // RND then only depends on the save state, which rewind, run-ahead and netplay need
+            core.emu.seed(time_seed());
+            core.emu.load(rom);
+            core.crashed = false;
+            true
+        }
+        _ => false,
+    }
+}
+
+fn time_seed() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos() ^ time.as_secs() as u32)
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: u32,
+    _info: *const retro_game_info,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    if let Some(core) = CORE.lock().unwrap().as_mut() {
+        core.rom.clear();
+        core.emu.reset();
+        core.crashed = false;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> u32 {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    // The core isn't locked while calling back into the frontend, which may call into it again
+    let callbacks = callbacks();
+    let keys = match (callbacks.input_poll, callbacks.input_state) {
+        (Some(poll), Some(state)) => {
+            unsafe { poll() };
+            Some(array::from_fn(|key| unsafe { is_pressed(state, key) }))
+        }
+        _ => None,
+    };
+
+    let Some(frame) = run_frame(keys) else {
+        return;
+    };
+
+    if let Some(video_refresh) = callbacks.video_refresh {
+        unsafe {
+            video_refresh(
+                frame.as_ptr() as *const c_void,
+                SCREEN_WIDTH as u32,
+                SCREEN_HEIGHT as u32,
+                SCREEN_WIDTH * 4,
+            )
+        };
+    }
+
+    // Some frontends use audio to pace emulation, so feed them a frame worth of silence
+    if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+        let silence = [0i16; 2 * (SAMPLE_RATE as usize / 60)];
+        unsafe { audio_sample_batch(silence.as_ptr(), silence.len() / 2) };
+    }
+}
+
+// Runs the emulator for a frame and renders its display. A panic can't unwind into the frontend,
+// so it stops the emulator instead, which then shows a blank screen.
+fn run_frame(keys: Option<[bool; NUM_KEYS]>) -> Option<Frame> {
+    let mut guard = CORE.lock().unwrap();
+    let core = guard.as_mut()?;
+
+    if !core.crashed {
+        let emu = &mut core.emu;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            if let Some(keys) = keys {
+                for (key, pressed) in keys.into_iter().enumerate() {
+                    emu.keypress(key, pressed);
+                }
+            }
+            for _ in 0..TICKS_PER_FRAME {
+                emu.tick();
+            }
+            emu.tick_timers();
+        }));
+        if result.is_err() {
+            eprintln!("chip-r: the emulator crashed, reset or load a game to restart it");
+            core.crashed = true;
+        }
+    }
+
+    let mut frame = [BACKGROUND; SCREEN_WIDTH * SCREEN_HEIGHT];
+    if !core.crashed {
+        for (pixel, &lit) in frame.iter_mut().zip(core.emu.get_display()) {
+            *pixel = if lit { FOREGROUND } else { BACKGROUND };
+        }
+    }
+    Some(frame)
+}
+
+// Keyboard uses the same layout as the SDL frontend, the joypad covers the keys most games use
+unsafe fn is_pressed(state: retro_input_state_t, key: usize) -> bool {
+    let keyboard = match key {
+        0x1 => b'1',
+        0x2 => b'2',
+        0x3 => b'3',
+        0xC => b'4',
+        0x4 => b'q',
+        0x5 => b'w',
+        0x6 => b'e',
+        0xD => b'r',
+        0x7 => b'a',
+        0x8 => b's',
+        0x9 => b'd',
+        0xE => b'f',
+        0xA => b'z',
+        0x0 => b'x',
+        0xB => b'c',
+        _ => b'v',
+    };
+    let joypad = match key {
+        0x2 => Some(RETRO_DEVICE_ID_JOYPAD_UP),
+        0x8 => Some(RETRO_DEVICE_ID_JOYPAD_DOWN),
+        0x4 => Some(RETRO_DEVICE_ID_JOYPAD_LEFT),
+        0x6 => Some(RETRO_DEVICE_ID_JOYPAD_RIGHT),
+        0x5 => Some(RETRO_DEVICE_ID_JOYPAD_A),
+        0x0 => Some(RETRO_DEVICE_ID_JOYPAD_B),
+        0xA => Some(RETRO_DEVICE_ID_JOYPAD_X),
+        0xB => Some(RETRO_DEVICE_ID_JOYPAD_Y),
+        0xF => Some(RETRO_DEVICE_ID_JOYPAD_START),
+        0xE => Some(RETRO_DEVICE_ID_JOYPAD_SELECT),
+        _ => None,
+    };
+
+    state(0, RETRO_DEVICE_KEYBOARD, 0, keyboard as u32) != 0
+        || joypad.is_some_and(|id| state(0, RETRO_DEVICE_JOYPAD, 0, id) != 0)
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    STATE_SIZE
+}
+
+/// # Safety
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let buffer = slice::from_raw_parts_mut(data as *mut u8, size);
+    match CORE.lock().unwrap().as_ref() {
+        Some(core) => core.emu.save_state(buffer).is_ok(),
+        None => false,
+    }
+}
+
+/// # Safety
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let buffer = slice::from_raw_parts(data as *const u8, size);
+    match CORE.lock().unwrap().as_mut() {
+        Some(core) => {
+            let loaded = core.emu.load_state(buffer).is_ok();
+            core.crashed &= !loaded;
+            loaded
+        }
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: u32, _enabled: bool, _code: *const c_char) {}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: u32) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: u32) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ptr;
+
+    // Draws font sprites of random digits at random places, forever
+    #[rustfmt::skip]
+    const RANDOM_DIGITS: [u8; 12] = [
+        0xC0, 0x3F, // RND V0, 0x3F
+        0xC1, 0x1F, // RND V1, 0x1F
+        0xC2, 0x0F, // RND V2, 0x0F
+        0xF2, 0x29, // LDF V2
+        0xD0, 0x15, // DRAW V0, V1, 5
+        0x12, 0x00, // JMP 0x200
+    ];
+
+    // Calls itself until the stack overflows
+    const RECURSE: [u8; 2] = [0x22, 0x00]; // CALL 0x200
+
+    // The core is a global, so tests take turns
+    static SERIAL: Mutex<()> = Mutex::new(());
+    static FRAMES: Mutex<Vec<Vec<u32>>> = Mutex::new(Vec::new());
+
+    unsafe extern "C" fn environment(_cmd: u32, _data: *mut c_void) -> bool {
+        true
+    }
+
+    unsafe extern "C" fn video_refresh(data: *const c_void, width: u32, height: u32, pitch: usize) {
+        assert_eq!(pitch, width as usize * 4);
+        let frame = slice::from_raw_parts(data as *const u32, (width * height) as usize);
+        FRAMES.lock().unwrap().push(frame.to_vec());
+    }
+
+    // Saves a state on every frame, like run-ahead does
+    unsafe extern "C" fn video_refresh_and_save(
+        data: *const c_void,
+        width: u32,
+        height: u32,
+        pitch: usize,
+    ) {
+        video_refresh(data, width, height, pitch);
+        let mut state = vec![0; retro_serialize_size()];
+        assert!(retro_serialize(
+            state.as_mut_ptr() as *mut c_void,
+            state.len()
+        ));
+    }
+
+    unsafe extern "C" fn input_poll() {}
+
+    unsafe extern "C" fn input_state(_port: u32, _device: u32, _index: u32, _id: u32) -> i16 {
+        0
+    }
+
+    // Starts the core from scratch, as a frontend would, and loads the ROM
+    fn start(rom: &[u8]) {
+        retro_set_environment(environment);
+        retro_set_video_refresh(video_refresh);
+        retro_set_input_poll(input_poll);
+        retro_set_input_state(input_state);
+        retro_init();
+        let game = retro_game_info {
+            path: ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: ptr::null(),
+        };
+        assert!(unsafe { retro_load_game(&game) });
+    }
+
+    fn run(frames: usize) -> Vec<Vec<u32>> {
+        FRAMES.lock().unwrap().clear();
+        for _ in 0..frames {
+            retro_run();
+        }
+        std::mem::take(&mut *FRAMES.lock().unwrap())
+    }
+
+    #[test]
+    fn save_states_replay_in_a_fresh_core() {
+        let _serial = SERIAL.lock().unwrap();
+        start(&RANDOM_DIGITS);
+        run(30);
+        let mut state = vec![0; retro_serialize_size()];
+        assert!(unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) });
+        let played = run(30);
+        assert!(unsafe { !retro_serialize(state.as_mut_ptr() as *mut c_void, 16) });
+        retro_unload_game();
+        retro_deinit();
+
+        start(&RANDOM_DIGITS);
+        assert!(unsafe { retro_unserialize(state.as_ptr() as *const c_void, state.len()) });
+        let replayed = run(30);
+        assert!(unsafe { !retro_unserialize(state.as_ptr() as *const c_void, 16) });
+        retro_deinit();
+
+        assert_eq!(played.len(), 30);
+        assert!(played == replayed, "the replay diverged");
+    }
+
+    #[test]
+    fn callbacks_can_call_into_the_core() {
+        let _serial = SERIAL.lock().unwrap();
+        start(&RANDOM_DIGITS);
+        retro_set_video_refresh(video_refresh_and_save);
+        assert_eq!(run(3).len(), 3);
+        retro_deinit();
+    }
+
+    #[test]
+    fn a_crash_blanks_the_screen_until_reset() {
+        let _serial = SERIAL.lock().unwrap();
+        panic::set_hook(Box::new(|_| {}));
+        start(&RECURSE);
+        let frames = run(3);
+        let _ = panic::take_hook();
+
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().flatten().all(|&pixel| pixel == BACKGROUND));
+        assert!(CORE.lock().unwrap().as_ref().unwrap().crashed);
+
+        retro_reset();
+        assert!(!CORE.lock().unwrap().as_ref().unwrap().crashed);
+        retro_deinit();
+    }
+}
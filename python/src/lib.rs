@@ -0,0 +1,211 @@
+use chip8::*;
+
+use numpy::ndarray::ArrayView2;
+use numpy::PyArray2;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+const MAX_ROM_SIZE: usize = RAM_SIZE - 0x200; // RAM left after the 0x200 program start
+
+/// Python handle to an emulator. The screen is handed out as a NumPy array over the emulator's
+/// own memory, so it follows the emulator without being copied every frame.
+#[pyclass(name = "Emulator")]
+pub struct PyEmulator {
+    emu: Emulator,
+}
+
+#[pymethods]
+impl PyEmulator {
+    #[new]
+    fn new() -> Self {
+        Self {
+            emu: Emulator::new(),
+        }
+    }
+
+    fn load(&mut self, data: &[u8]) -> PyResult<()> {
+        if data.len() > MAX_ROM_SIZE {
+            return Err(PyValueError::new_err(format!(
+                "ROM too large: {} bytes, at most {} fit",
+                data.len(),
+                MAX_ROM_SIZE
+            )));
+        }
+        self.emu.load(data);
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.emu.reset();
+    }
+
+    /// Seeds RND, so runs replay the same.
+    fn seed(&mut self, seed: u32) {
+        self.emu.seed(seed);
+    }
+
+    /// Runs `count` instructions, one by default.
+    #[pyo3(signature = (count = 1))]
+    fn tick(&mut self, count: usize) {
+        for _ in 0..count {
+            self.emu.tick();
+        }
+    }
+
+    fn tick_timers(&mut self) {
+        self.emu.tick_timers();
+    }
+
+    fn keypress(&mut self, key: usize, pressed: bool) -> PyResult<()> {
+        if key >= NUM_KEYS {
+            return Err(PyValueError::new_err(format!("No key {}", key)));
+        }
+        self.emu.keypress(key, pressed);
+        Ok(())
+    }
+
+    fn display_changed(&mut self) -> bool {
+        self.emu.display_changed()
+    }
+
+    /// The screen as a read-only boolean array of shape (height, width). It's a view of the
+    /// emulator's memory rather than a copy: ticking the emulator updates it.
+    fn get_display<'py>(this: Bound<'py, Self>) -> PyResult<Bound<'py, PyArray2<bool>>> {
+        let py = this.py();
+        // Raises an ImportError, rather than panicking, when NumPy isn't installed
+        py.import("numpy")?;
+        let display = {
+            let emulator = this.borrow();
+            let screen = emulator.emu.get_display();
+            let view = ArrayView2::from_shape((SCREEN_HEIGHT, SCREEN_WIDTH), screen)
+                .expect("The screen holds SCREEN_HEIGHT rows of SCREEN_WIDTH pixels.");
+            // SAFETY: the screen is an array inside the emulator, which never moves or gets
+            // reallocated, and the NumPy array keeps the emulator alive
+            unsafe { PyArray2::borrow_from_array(&view, this.clone().into_any()) }
+        };
+
+        // Writes would bypass the emulator's bookkeeping of what changed
+        let flags = PyDict::new(py);
+        flags.set_item("write", false)?;
+        display.call_method("setflags", (), Some(&flags))?;
+        Ok(display)
+    }
+
+    fn save_state<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        let mut state = vec![0; STATE_SIZE];
+        self.emu
+            .save_state(&mut state)
+            .expect("The buffer holds STATE_SIZE bytes.");
+        PyBytes::new(py, &state)
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        self.emu
+            .load_state(state)
+            .map_err(|error| PyValueError::new_err(format!("Invalid state: {:?}", error)))
+    }
+
+    /// Copies `length` bytes of RAM, starting at `address`.
+    fn read_memory<'py>(
+        &self,
+        py: Python<'py>,
+        address: usize,
+        length: usize,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        check_range(address, length)?;
+        Ok(PyBytes::new(py, self.emu.memory(address..address + length)))
+    }
+
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> PyResult<()> {
+        check_range(address, data.len())?;
+        self.emu.write_memory(address, data);
+        Ok(())
+    }
+}
+
+fn check_range(address: usize, length: usize) -> PyResult<()> {
+    match address.checked_add(length) {
+        Some(end) if end <= RAM_SIZE => Ok(()),
+        _ => Err(PyValueError::new_err(format!(
+            "{} bytes at {:#X} go past the end of the RAM",
+            length, address
+        ))),
+    }
+}
+
+#[pymodule]
+fn chip_r(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyEmulator>()?;
+    module.add("SCREEN_WIDTH", SCREEN_WIDTH)?;
+    module.add("SCREEN_HEIGHT", SCREEN_HEIGHT)?;
+    module.add("RAM_SIZE", RAM_SIZE)?;
+    module.add("NUM_KEYS", NUM_KEYS)?;
+    module.add("STATE_SIZE", STATE_SIZE)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs through the module the way a script would, in an embedded interpreter. NumPy may not
+    // be installed where the tests run, in which case `get_display` has to raise.
+    const SMOKE_TEST: &std::ffi::CStr = cr#"
+import chip_r
+
+emu = chip_r.Emulator()
+# LDF V0, DRAW V0, V0, 5, then spins: draws the 0 of the font at (0, 0)
+emu.load(bytes([0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04]))
+emu.tick(3)
+assert emu.display_changed()
+assert not emu.display_changed()
+assert emu.read_memory(0x200, 2) == b"\xF0\x29"
+
+try:
+    import numpy
+except ImportError:
+    numpy = None
+
+if numpy is None:
+    try:
+        emu.get_display()
+        raise AssertionError("get_display worked without NumPy")
+    except ImportError:
+        pass
+else:
+    display = emu.get_display()
+    assert display.shape == (chip_r.SCREEN_HEIGHT, chip_r.SCREEN_WIDTH)
+    assert display[0, :5].tolist() == [True, True, True, True, False]
+    assert display.sum() == 14
+    assert not display.flags.writeable
+    emu.reset()
+    assert not display.any()
+
+state = emu.save_state()
+assert len(state) == chip_r.STATE_SIZE
+emu.tick(100)
+emu.load_state(state)
+assert emu.save_state() == state
+
+for call in (lambda: emu.load(bytes(4096)), lambda: emu.keypress(16, True),
+             lambda: emu.read_memory(0xFFF, 2), lambda: emu.load_state(b"")):
+    try:
+        call()
+        raise AssertionError("no ValueError")
+    except ValueError:
+        pass
+"#;
+
+    #[test]
+    fn runs_a_rom_from_python() {
+        pyo3::append_to_inittab!(chip_r);
+        Python::initialize();
+        Python::attach(|py| {
+            if let Err(error) = py.run(SMOKE_TEST, None, None) {
+                error.display(py);
+                panic!("the smoke test failed: {}", error);
+            }
+        });
+    }
+}
@@ -0,0 +1,79 @@
+use chip8::*;
+
+use wasm_bindgen::prelude::*;
+
+const MAX_ROM_SIZE: usize = RAM_SIZE - 0x200; // RAM left after the 0x200 program start
+
+/// Browser handle to an emulator. The screen is read straight from the wasm memory, through
+/// `display_ptr()`, without copying it to JavaScript every frame.
+#[wasm_bindgen]
+pub struct Chip8 {
+    emu: Emulator,
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            emu: Emulator::new(),
+        }
+    }
+
+    /// Throws if the ROM doesn't fit in the RAM.
+    pub fn load(&mut self, data: &[u8]) -> Result<(), JsError> {
+        if data.len() > MAX_ROM_SIZE {
+            return Err(JsError::new(&format!(
+                "ROM too large: {} bytes, at most {} fit",
+                data.len(),
+                MAX_ROM_SIZE
+            )));
+        }
+        self.emu.load(data);
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.emu.reset();
+    }
+
+    pub fn tick(&mut self) {
+        self.emu.tick();
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.emu.tick_timers();
+    }
+
+    /// Throws for keys past 0xF.
+    pub fn keypress(&mut self, key: usize, pressed: bool) -> Result<(), JsError> {
+        if key >= NUM_KEYS {
+            return Err(JsError::new(&format!("No key {}", key)));
+        }
+        self.emu.keypress(key, pressed);
+        Ok(())
+    }
+
+    pub fn display_changed(&mut self) -> bool {
+        self.emu.display_changed()
+    }
+
+    /// Address of the screen in the wasm memory: one byte per pixel, 0 or 1, row by row.
+    pub fn display_ptr(&self) -> *const bool {
+        self.emu.get_display().as_ptr()
+    }
+
+    pub fn display_width() -> usize {
+        SCREEN_WIDTH
+    }
+
+    pub fn display_height() -> usize {
+        SCREEN_HEIGHT
+    }
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,73 @@
+//! Run with `wasm-pack test --node`.
+
+use chip8::{RAM_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
+use wasm::Chip8;
+
+use wasm_bindgen_test::*;
+
+// Draws the font sprite of 0 at (0, 0): LDF V0, DRAW V0, V0, 5, then spins
+const DRAW_ZERO: [u8; 6] = [0xF0, 0x29, 0xD0, 0x05, 0x12, 0x04];
+
+fn display(emulator: &Chip8) -> &[bool] {
+    let size = Chip8::display_width() * Chip8::display_height();
+    unsafe { std::slice::from_raw_parts(emulator.display_ptr(), size) }
+}
+
+#[wasm_bindgen_test]
+fn display_has_the_screen_size() {
+    assert_eq!(Chip8::display_width(), SCREEN_WIDTH);
+    assert_eq!(Chip8::display_height(), SCREEN_HEIGHT);
+}
+
+#[wasm_bindgen_test]
+fn draws_to_the_display() {
+    let mut emulator = Chip8::new();
+    emulator.load(&DRAW_ZERO).unwrap();
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    assert!(emulator.display_changed());
+    assert!(!emulator.display_changed());
+
+    // The top row of the 0 sprite is 0xF0
+    let display = display(&emulator);
+    assert_eq!(&display[..5], &[true, true, true, true, false]);
+    assert_eq!(display.iter().filter(|&&pixel| pixel).count(), 14);
+}
+
+#[wasm_bindgen_test]
+fn reset_clears_the_display() {
+    let mut emulator = Chip8::new();
+    emulator.load(&DRAW_ZERO).unwrap();
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    emulator.reset();
+    assert!(display(&emulator).iter().all(|&pixel| !pixel));
+}
+
+#[wasm_bindgen_test]
+fn waits_for_a_key() {
+    // WKP V0, then draws the sprite of the key
+    let mut emulator = Chip8::new();
+    emulator
+        .load(&[0xF0, 0x0A, 0xF0, 0x29, 0xD1, 0x15])
+        .unwrap();
+    emulator.tick();
+    emulator.tick();
+    emulator.keypress(1, true).unwrap();
+    for _ in 0..3 {
+        emulator.tick();
+    }
+    assert!(display(&emulator).iter().any(|&pixel| pixel));
+}
+
+#[wasm_bindgen_test]
+fn rejects_roms_and_keys_that_dont_fit() {
+    let mut emulator = Chip8::new();
+    assert!(emulator.load(&[0; RAM_SIZE - 0x200]).is_ok());
+    assert!(emulator.load(&[0; RAM_SIZE - 0x200 + 1]).is_err());
+
+    assert!(emulator.keypress(0xF, true).is_ok());
+    assert!(emulator.keypress(0x10, true).is_err());
+}